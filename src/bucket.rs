@@ -0,0 +1,32 @@
+use std::{fmt, path::PathBuf};
+
+use chrono::{DateTime, Datelike, TimeZone};
+
+/// Predefined directory layouts to sort photos into.
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+pub enum BucketBy {
+    /// `%Y`
+    Year,
+    /// `%Y/%m`
+    YearMonth,
+    /// `%Y/W%W`
+    YearWeek,
+    /// `%Y/Q1` to `%Y/Q4`
+    YearQuarter,
+}
+
+/// Returns the directory, relative to the source directory, a photo taken
+/// at `datetime` belongs to.
+pub fn bucket_path<T>(datetime: &DateTime<T>, bucket: BucketBy) -> PathBuf
+where
+    T: TimeZone,
+    T::Offset: fmt::Display,
+{
+    let path = match bucket {
+        BucketBy::Year => datetime.format("%Y").to_string(),
+        BucketBy::YearMonth => datetime.format("%Y/%m").to_string(),
+        BucketBy::YearWeek => datetime.format("%Y/W%W").to_string(),
+        BucketBy::YearQuarter => format!("{}/Q{}", datetime.year(), datetime.month0() / 3 + 1),
+    };
+    PathBuf::from(path)
+}
@@ -0,0 +1,94 @@
+use std::{borrow::Cow, io};
+
+fn quote(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Writes a CSV record, quoting fields as described in RFC 4180.
+pub fn write_record<W>(writer: &mut W, fields: &[&str]) -> io::Result<()>
+where
+    W: io::Write,
+{
+    for (i, field) in fields.iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+        write!(writer, "{}", quote(field))?;
+    }
+    write!(writer, "\r\n")
+}
+
+/// Reads CSV records, unquoting fields as described in RFC 4180.
+pub fn read_records(contents: &str) -> io::Result<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if field.is_empty() => in_quotes = true,
+            ',' if !in_quotes => record.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes && chars.peek() == Some(&'\n') => {}
+            '\n' if !in_quotes => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Unterminated quoted CSV field",
+        ));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_records() {
+        let records = [
+            vec!["plain", "with, comma", "with \"quotes\""],
+            vec!["with\r\nCRLF", "", "with\nnewline"],
+        ];
+        let mut contents = Vec::new();
+        for record in &records {
+            write_record(&mut contents, record).unwrap();
+        }
+        let contents = String::from_utf8(contents).unwrap();
+        assert!(contents.starts_with("plain,\"with, comma\",\"with \"\"quotes\"\"\"\r\n"));
+        assert_eq!(read_records(&contents).unwrap(), records);
+    }
+
+    #[test]
+    fn reads_records_without_trailing_newline() {
+        assert_eq!(
+            read_records("a,b\r\nc,\"d\"").unwrap(),
+            [vec!["a", "b"], vec!["c", "d"]]
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_quotes() {
+        let err = read_records("a,\"b\r\n").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
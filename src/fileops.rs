@@ -0,0 +1,191 @@
+use std::{fs, io, path::Path};
+
+use filetime::FileTime;
+
+/// The filesystem operations renames are applied with.
+pub trait FileOps {
+    fn rename(&self, source_path: &Path, target_path: &Path) -> io::Result<()>;
+    fn hard_link(&self, source_path: &Path, target_path: &Path) -> io::Result<()>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    fn modified(&self, path: &Path) -> io::Result<FileTime>;
+    fn set_modified(&self, path: &Path, mtime: FileTime) -> io::Result<()>;
+    /// Moves a file to the trash, returning whether it existed.
+    fn trash(&self, path: &Path) -> io::Result<bool>;
+}
+
+/// Applies operations to the real filesystem, through `std::fs`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdFileOps;
+
+impl FileOps for StdFileOps {
+    fn rename(&self, source_path: &Path, target_path: &Path) -> io::Result<()> {
+        fs::rename(source_path, target_path)
+    }
+
+    fn hard_link(&self, source_path: &Path, target_path: &Path) -> io::Result<()> {
+        fs::hard_link(source_path, target_path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn modified(&self, path: &Path) -> io::Result<FileTime> {
+        Ok(FileTime::from_last_modification_time(&fs::metadata(path)?))
+    }
+
+    fn set_modified(&self, path: &Path, mtime: FileTime) -> io::Result<()> {
+        filetime::set_file_mtime(path, mtime)
+    }
+
+    fn trash(&self, path: &Path) -> io::Result<bool> {
+        if fs::symlink_metadata(path).is_err() {
+            return Ok(false);
+        }
+        trash::delete(path).map_err(|err| {
+            io::Error::other(format!(
+                "Can't move {} to the trash: {}",
+                path.display(),
+                err
+            ))
+        })?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+pub(crate) use mem::MemFileOps;
+
+#[cfg(test)]
+mod mem {
+    use std::{
+        cell::RefCell,
+        collections::{BTreeMap, BTreeSet},
+        io,
+        path::{Path, PathBuf},
+    };
+
+    use filetime::FileTime;
+
+    use super::FileOps;
+
+    /// An in-memory filesystem, holding files by path along with their
+    /// modification time.
+    #[derive(Debug, Default)]
+    pub(crate) struct MemFileOps {
+        pub(crate) files: RefCell<BTreeMap<PathBuf, FileTime>>,
+        pub(crate) dirs: RefCell<BTreeSet<PathBuf>>,
+        pub(crate) trashed: RefCell<Vec<PathBuf>>,
+        /// Errors the next renames of these sources fail with, in order.
+        pub(crate) rename_errors: RefCell<BTreeMap<PathBuf, Vec<io::ErrorKind>>>,
+    }
+
+    impl MemFileOps {
+        pub(crate) fn with_files<P>(paths: &[P]) -> Self
+        where
+            P: AsRef<Path>,
+        {
+            let ops = Self::default();
+            for path in paths {
+                ops.files
+                    .borrow_mut()
+                    .insert(path.as_ref().to_path_buf(), FileTime::zero());
+            }
+            ops
+        }
+
+        pub(crate) fn has_file<P>(&self, path: P) -> bool
+        where
+            P: AsRef<Path>,
+        {
+            self.files.borrow().contains_key(path.as_ref())
+        }
+
+        /// Makes the next renames of `path` fail with `kinds`, in order.
+        pub(crate) fn fail_rename<P>(&self, path: P, kinds: &[io::ErrorKind])
+        where
+            P: AsRef<Path>,
+        {
+            self.rename_errors
+                .borrow_mut()
+                .entry(path.as_ref().to_path_buf())
+                .or_default()
+                .extend(kinds);
+        }
+
+        fn check_target(&self, path: &Path) -> io::Result<()> {
+            if self.dirs.borrow().contains(path) {
+                return Err(io::ErrorKind::IsADirectory.into());
+            }
+            Ok(())
+        }
+
+        fn mtime(&self, path: &Path) -> io::Result<FileTime> {
+            self.files
+                .borrow()
+                .get(path)
+                .copied()
+                .ok_or_else(|| io::ErrorKind::NotFound.into())
+        }
+    }
+
+    impl FileOps for MemFileOps {
+        fn rename(&self, source_path: &Path, target_path: &Path) -> io::Result<()> {
+            if let Some(kinds) = self.rename_errors.borrow_mut().get_mut(source_path) {
+                if !kinds.is_empty() {
+                    return Err(kinds.remove(0).into());
+                }
+            }
+            let mtime = self.mtime(source_path)?;
+            self.check_target(target_path)?;
+            let mut files = self.files.borrow_mut();
+            files.remove(source_path);
+            files.insert(target_path.to_path_buf(), mtime);
+            Ok(())
+        }
+
+        fn hard_link(&self, source_path: &Path, target_path: &Path) -> io::Result<()> {
+            let mtime = self.mtime(source_path)?;
+            self.check_target(target_path)?;
+            if self.has_file(target_path) {
+                return Err(io::ErrorKind::AlreadyExists.into());
+            }
+            self.files
+                .borrow_mut()
+                .insert(target_path.to_path_buf(), mtime);
+            Ok(())
+        }
+
+        fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+            let mut dirs = self.dirs.borrow_mut();
+            for ancestor in path.ancestors() {
+                if !ancestor.as_os_str().is_empty() {
+                    dirs.insert(ancestor.to_path_buf());
+                }
+            }
+            Ok(())
+        }
+
+        fn modified(&self, path: &Path) -> io::Result<FileTime> {
+            self.mtime(path)
+        }
+
+        fn set_modified(&self, path: &Path, mtime: FileTime) -> io::Result<()> {
+            match self.files.borrow_mut().get_mut(path) {
+                Some(file_mtime) => {
+                    *file_mtime = mtime;
+                    Ok(())
+                }
+                None => Err(io::ErrorKind::NotFound.into()),
+            }
+        }
+
+        fn trash(&self, path: &Path) -> io::Result<bool> {
+            if self.files.borrow_mut().remove(path).is_none() {
+                return Ok(false);
+            }
+            self.trashed.borrow_mut().push(path.to_path_buf());
+            Ok(true)
+        }
+    }
+}
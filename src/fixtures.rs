@@ -0,0 +1,362 @@
+//! Builders for the image files the tests read.
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use exif::{Context, In, Tag};
+
+/// A directory removed along with its contents when dropped.
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn new() -> Self {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let path = env::temp_dir().join(format!(
+            "namexif-test-{}-{}",
+            process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Writes a file at `name` in the directory, returning its path.
+    pub(crate) fn write<C>(&self, name: &str, contents: C) -> PathBuf
+    where
+        C: AsRef<[u8]>,
+    {
+        let path = self.0.join(name);
+        if let Some(parent_path) = path.parent() {
+            fs::create_dir_all(parent_path).unwrap();
+        }
+        fs::write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub(crate) enum Value {
+    Byte(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+}
+
+impl Value {
+    pub(crate) fn ascii(s: &str) -> Self {
+        Self::Ascii(s.to_string())
+    }
+
+    fn type_and_count(&self) -> (u16, usize) {
+        match self {
+            Self::Byte(values) => (1, values.len()),
+            Self::Ascii(s) => (2, s.len() + 1),
+            Self::Short(values) => (3, values.len()),
+            Self::Long(values) => (4, values.len()),
+            Self::Rational(values) => (5, values.len()),
+        }
+    }
+
+    fn encode(&self, order: ByteOrder) -> Vec<u8> {
+        match self {
+            Self::Byte(values) => values.clone(),
+            Self::Ascii(s) => s.bytes().chain([0]).collect(),
+            Self::Short(values) => values.iter().flat_map(|v| order.u16(*v)).collect(),
+            Self::Long(values) => values.iter().flat_map(|v| order.u32(*v)).collect(),
+            Self::Rational(values) => values
+                .iter()
+                .flat_map(|(num, denom)| [order.u32(*num), order.u32(*denom)])
+                .flatten()
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum ByteOrder {
+    Big,
+    Little,
+}
+
+impl ByteOrder {
+    fn u16(self, value: u16) -> [u8; 2] {
+        match self {
+            Self::Big => value.to_be_bytes(),
+            Self::Little => value.to_le_bytes(),
+        }
+    }
+
+    fn u32(self, value: u32) -> [u8; 4] {
+        match self {
+            Self::Big => value.to_be_bytes(),
+            Self::Little => value.to_le_bytes(),
+        }
+    }
+}
+
+type Entries = Vec<(u16, Value)>;
+
+/// The fields of a single image file directory, and of its sub-IFDs.
+#[derive(Debug, Default)]
+struct Ifds {
+    tiff: Entries,
+    exif: Entries,
+    gps: Entries,
+    interop: Entries,
+}
+
+const EXIF_IFD_POINTER: u16 = 0x8769;
+const GPS_IFD_POINTER: u16 = 0x8825;
+const INTEROP_IFD_POINTER: u16 = 0xa005;
+
+/// Builds TIFF structures, as found in TIFF and raw files, or in the APP1
+/// segment of JPEG files.
+#[derive(Debug, Default)]
+pub(crate) struct Tiff {
+    little_endian: bool,
+    ifds: Vec<Ifds>,
+}
+
+impl Tiff {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn little_endian(mut self) -> Self {
+        self.little_endian = true;
+        self
+    }
+
+    /// Adds a field to the `ifd_num`-th IFD, or to its sub-IFD matching the
+    /// context of the tag.
+    pub(crate) fn field(mut self, ifd_num: In, tag: Tag, value: Value) -> Self {
+        let index = ifd_num.index() as usize;
+        if self.ifds.len() <= index {
+            self.ifds.resize_with(index + 1, Ifds::default);
+        }
+        let ifds = &mut self.ifds[index];
+        let entries = match tag.context() {
+            Context::Tiff => &mut ifds.tiff,
+            Context::Exif => &mut ifds.exif,
+            Context::Gps => &mut ifds.gps,
+            Context::Interop => &mut ifds.interop,
+            _ => unreachable!("unknown context"),
+        };
+        entries.push((tag.number(), value));
+        self
+    }
+
+    /// Adds a `DateTimeOriginal` field to the primary image.
+    pub(crate) fn date_time_original(self, datetime: &str) -> Self {
+        self.field(In::PRIMARY, Tag::DateTimeOriginal, Value::ascii(datetime))
+    }
+
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let order = if self.little_endian {
+            ByteOrder::Little
+        } else {
+            ByteOrder::Big
+        };
+        // Lay out every IFD after the header: each main IFD, even if empty,
+        // followed by its Exif, interoperability and GPS IFDs, if any.
+        // Pointers are stored inline, so the sizes are known before the
+        // offsets are.
+        let mut layout: Vec<Entries> = Vec::new();
+        let primary = [Ifds::default()];
+        let ifds = if self.ifds.is_empty() {
+            &primary[..]
+        } else {
+            &self.ifds[..]
+        };
+        for ifds in ifds {
+            let mut tiff = ifds.tiff.clone();
+            let mut exif = ifds.exif.clone();
+            if !ifds.exif.is_empty() || !ifds.interop.is_empty() {
+                tiff.push((EXIF_IFD_POINTER, Value::Long(vec![0])));
+            }
+            if !ifds.gps.is_empty() {
+                tiff.push((GPS_IFD_POINTER, Value::Long(vec![0])));
+            }
+            if !ifds.interop.is_empty() {
+                exif.push((INTEROP_IFD_POINTER, Value::Long(vec![0])));
+            }
+            layout.push(tiff);
+            layout.push(exif);
+            layout.push(ifds.interop.clone());
+            layout.push(ifds.gps.clone());
+        }
+        let is_written =
+            |index: usize, entries: &Entries| index.is_multiple_of(4) || !entries.is_empty();
+        let mut offsets = Vec::with_capacity(layout.len());
+        let mut offset = 8;
+        for (index, entries) in layout.iter().enumerate() {
+            offsets.push(offset);
+            if is_written(index, entries) {
+                offset += ifd_len(entries, order);
+            }
+        }
+        for (ifd_index, chunk) in layout.chunks_mut(4).enumerate() {
+            let base = ifd_index * 4;
+            let (tiff, sub_ifds) = chunk.split_at_mut(1);
+            for (tag, value) in tiff[0].iter_mut().chain(sub_ifds[0].iter_mut()) {
+                let pointed = match *tag {
+                    EXIF_IFD_POINTER => base + 1,
+                    INTEROP_IFD_POINTER => base + 2,
+                    GPS_IFD_POINTER => base + 3,
+                    _ => continue,
+                };
+                *value = Value::Long(vec![offsets[pointed] as u32]);
+            }
+        }
+
+        let mut bytes = match order {
+            ByteOrder::Big => b"MM".to_vec(),
+            ByteOrder::Little => b"II".to_vec(),
+        };
+        bytes.extend(order.u16(42));
+        bytes.extend(order.u32(8));
+        for (index, entries) in layout.iter().enumerate() {
+            if !is_written(index, entries) {
+                continue;
+            }
+            // Only main IFDs are chained.
+            let next = if index.is_multiple_of(4) && index + 4 < layout.len() {
+                offsets[index + 4] as u32
+            } else {
+                0
+            };
+            write_ifd(&mut bytes, entries, next, order);
+        }
+        bytes
+    }
+
+    /// Wraps the TIFF structure in a minimal JPEG file.
+    pub(crate) fn to_jpeg(&self) -> Vec<u8> {
+        let mut payload = b"Exif\0\0".to_vec();
+        payload.extend(self.to_bytes());
+        jpeg(&[&payload])
+    }
+}
+
+/// Builds a JPEG file made of APP1 segments with `payloads`, and no image.
+pub(crate) fn jpeg(payloads: &[&[u8]]) -> Vec<u8> {
+    let mut bytes = vec![0xff, 0xd8];
+    for payload in payloads {
+        bytes.extend([0xff, 0xe1]);
+        bytes.extend((payload.len() as u16 + 2).to_be_bytes());
+        bytes.extend(*payload);
+    }
+    bytes.extend([0xff, 0xd9]);
+    bytes
+}
+
+/// Builds an XMP packet made of `properties`, e.g.
+/// `photoshop:DateCreated="2023-05-01T12:00:00"`, as found in sidecars.
+pub(crate) fn xmp_packet(properties: &str) -> String {
+    format!(
+        concat!(
+            "<x:xmpmeta xmlns:x=\"adobe:ns:meta/\"><rdf:RDF>",
+            "<rdf:Description {}/>",
+            "</rdf:RDF></x:xmpmeta>"
+        ),
+        properties
+    )
+}
+
+/// Builds the APP1 payload of an XMP packet made of `properties`.
+pub(crate) fn xmp(properties: &str) -> Vec<u8> {
+    let mut payload = b"http://ns.adobe.com/xap/1.0/\0".to_vec();
+    payload.extend(xmp_packet(properties).into_bytes());
+    payload
+}
+
+fn padded_len(len: usize) -> usize {
+    len + len % 2
+}
+
+fn ifd_len(entries: &Entries, order: ByteOrder) -> usize {
+    let data_len: usize = entries
+        .iter()
+        .map(|(_, value)| value.encode(order).len())
+        .filter(|len| *len > 4)
+        .map(padded_len)
+        .sum();
+    2 + 12 * entries.len() + 4 + data_len
+}
+
+fn write_ifd(bytes: &mut Vec<u8>, entries: &Entries, next: u32, order: ByteOrder) {
+    let mut entries = entries.clone();
+    entries.sort_by_key(|(tag, _)| *tag);
+    let mut data_offset = bytes.len() + 2 + 12 * entries.len() + 4;
+    let mut data = Vec::new();
+    bytes.extend(order.u16(entries.len() as u16));
+    for (tag, value) in &entries {
+        let (value_type, count) = value.type_and_count();
+        let mut encoded = value.encode(order);
+        bytes.extend(order.u16(*tag));
+        bytes.extend(order.u16(value_type));
+        bytes.extend(order.u32(count as u32));
+        if encoded.len() <= 4 {
+            encoded.resize(4, 0);
+            bytes.extend(encoded);
+        } else {
+            bytes.extend(order.u32(data_offset as u32));
+            encoded.resize(padded_len(encoded.len()), 0);
+            data_offset += encoded.len();
+            data.extend(encoded);
+        }
+    }
+    bytes.extend(order.u32(next));
+    bytes.extend(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_readable_jpeg() {
+        for tiff in [Tiff::new(), Tiff::new().little_endian()] {
+            let bytes = tiff
+                .date_time_original("2024:01:02 03:04:05")
+                .field(In::PRIMARY, Tag::Software, Value::ascii("namexif"))
+                .field(In::PRIMARY, Tag::Orientation, Value::Short(vec![6]))
+                .field(In::PRIMARY, Tag::GPSAltitudeRef, Value::Byte(vec![1]))
+                .field(
+                    In::PRIMARY,
+                    Tag::GPSAltitude,
+                    Value::Rational(vec![(12, 1)]),
+                )
+                .field(In::THUMBNAIL, Tag::ImageWidth, Value::Long(vec![160]))
+                .to_jpeg();
+            assert!(bytes.len() < 256);
+            let exif = exif::Reader::new()
+                .read_from_container(&mut std::io::Cursor::new(bytes))
+                .unwrap();
+            let field = exif.get_field(Tag::DateTimeOriginal, In::PRIMARY).unwrap();
+            assert_eq!(field.display_value().to_string(), "2024-01-02 03:04:05");
+            assert!(exif.get_field(Tag::Software, In::PRIMARY).is_some());
+            let field = exif.get_field(Tag::Orientation, In::PRIMARY).unwrap();
+            assert_eq!(field.value.get_uint(0), Some(6));
+            let field = exif.get_field(Tag::GPSAltitudeRef, In::PRIMARY).unwrap();
+            assert_eq!(field.value.get_uint(0), Some(1));
+            assert!(exif.get_field(Tag::GPSAltitude, In::PRIMARY).is_some());
+            assert!(exif.get_field(Tag::ImageWidth, In::THUMBNAIL).is_some());
+        }
+    }
+}
@@ -1,6 +1,16 @@
-use std::{fs::File, io, path::Path, result};
+use std::{
+    ffi::{OsStr, OsString},
+    fmt,
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    result,
+};
 
-use chrono::{offset::LocalResult, DateTime, NaiveDate, NaiveDateTime, TimeZone};
+use chrono::{
+    offset::LocalResult, DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone,
+};
 use derive_more::{Display, Error, From};
 
 #[derive(Debug, Display, Error)]
@@ -15,22 +25,278 @@ pub enum TagError {
 pub enum DateError {
     #[display(fmt = "Invalid local date")]
     InvalidLocalDatetime,
-    #[display(fmt = "Ambiguous local date")]
-    AmbiguousLocalDatetime,
+    #[display(fmt = "Invalid UTC offset")]
+    InvalidOffset,
 }
 
 #[derive(Debug, Error, Display, From)]
 pub enum Error {
     Io(io::Error),
     Exif(exif::Error),
+    #[display(
+        fmt = "Failed to read EXIF from {} ({} bytes): {}",
+        "file_name.to_string_lossy()",
+        size,
+        source
+    )]
+    #[from(ignore)]
+    Read {
+        file_name: OsString,
+        size: u64,
+        source: exif::Error,
+    },
+    /// The file has a known image extension but is not in that format, e.g.
+    /// because it is corrupt or misnamed.
+    #[display(
+        fmt = "File {} does not appear to be a valid {} image: {}",
+        "file_name.to_string_lossy()",
+        "extension.to_uppercase()",
+        source
+    )]
+    #[from(ignore)]
+    InvalidFormat {
+        file_name: OsString,
+        extension: String,
+        source: exif::Error,
+    },
     Tag(TagError),
     Date(DateError),
-    #[display(fmt = "Date or time out of range")]
-    OutOfRange,
+    #[display(fmt = "File is locked by another process")]
+    FileLocked,
+    #[display(
+        fmt = "EXIF date {:04}-{:02}-{:02} {:02}:{:02}:{:02} is out of range",
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second
+    )]
+    OutOfRange {
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+    },
+}
+
+fn read_error(path: &Path, size: u64, source: exif::Error) -> Error {
+    let file_name = path.file_name().unwrap_or_default().to_os_string();
+    match (&source, path.extension().and_then(OsStr::to_str)) {
+        (exif::Error::InvalidFormat(_), Some(extension)) => Error::InvalidFormat {
+            file_name,
+            extension: extension.to_string(),
+            source,
+        },
+        _ => Error::Read {
+            file_name,
+            size,
+            source,
+        },
+    }
+}
+
+impl Error {
+    /// Returns whether the image carries no datetime at all.
+    pub fn is_missing_datetime(&self) -> bool {
+        matches!(
+            self,
+            Error::Read {
+                source: exif::Error::NotFound(_),
+                ..
+            } | Error::Tag(TagError::Missing)
+        )
+    }
+
+    fn io_error_kind(&self) -> Option<io::ErrorKind> {
+        match self {
+            Error::Io(err)
+            | Error::Exif(exif::Error::Io(err))
+            | Error::Read {
+                source: exif::Error::Io(err),
+                ..
+            } => Some(err.kind()),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the image could not be read for lack of permissions.
+    pub fn permission_denied(&self) -> bool {
+        self.io_error_kind() == Some(io::ErrorKind::PermissionDenied)
+    }
+
+    /// Returns whether the image file does not exist.
+    pub fn not_found(&self) -> bool {
+        self.io_error_kind() == Some(io::ErrorKind::NotFound)
+    }
+
+    /// Returns whether the image datetime is missing or cannot be parsed.
+    pub fn is_unusable_datetime(&self) -> bool {
+        self.is_missing_datetime()
+            || matches!(
+                self,
+                Error::Exif(_) | Error::Tag(_) | Error::OutOfRange { .. }
+            )
+    }
 }
 
 pub type Result<T> = result::Result<T, Error>;
 
+/// The Windows error raised when opening a file another process has open.
+#[cfg(windows)]
+const ERROR_SHARING_VIOLATION: i32 = 32;
+
+/// Returns whether an I/O error was caused by another process having the
+/// file open, which prevents opening or renaming it on Windows.
+#[cfg(windows)]
+pub fn is_sharing_violation(err: &io::Error) -> bool {
+    err.raw_os_error() == Some(ERROR_SHARING_VIOLATION)
+}
+
+/// Returns whether an I/O error was caused by another process having the
+/// file open, which only prevents opening or renaming it on Windows.
+#[cfg(not(windows))]
+pub fn is_sharing_violation(_err: &io::Error) -> bool {
+    false
+}
+
+fn open_file(path: &Path) -> Result<File> {
+    File::open(path).map_err(|err| {
+        if is_sharing_violation(&err) {
+            Error::FileLocked
+        } else {
+            Error::Io(err)
+        }
+    })
+}
+
+/// Returns whether another process holds an advisory lock on a file.
+#[cfg(unix)]
+pub fn is_locked(path: &Path) -> Result<bool> {
+    use std::os::fd::AsRawFd;
+
+    let file = open_file(path)?;
+    // SAFETY: `flock` is plain data, for which zeroes are valid.
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = libc::F_WRLCK as _;
+    lock.l_whence = libc::SEEK_SET as _;
+    // SAFETY: the descriptor is open for the duration of the call, and `lock`
+    // outlives it.
+    if unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETLK, &mut lock) } == -1 {
+        return Err(Error::Io(io::Error::last_os_error()));
+    }
+    Ok(lock.l_type != libc::F_UNLCK as _)
+}
+
+/// Returns whether another process holds an advisory lock on a file, which
+/// only Unix systems support.
+#[cfg(not(unix))]
+pub fn is_locked(_path: &Path) -> Result<bool> {
+    Ok(false)
+}
+
+/// The default capacity of the buffer EXIF data is read through.
+pub const DEFAULT_BUFFER_CAPACITY: usize = 64 * 1024;
+
+/// The size up to which files are read into memory in one go, rather than
+/// through a buffer, whatever its capacity.
+pub const IN_MEMORY_THRESHOLD: u64 = 64 * 1024;
+
+/// The tag photos are dated from.
+pub const DATETIME_TAG: exif::Tag = exif::Tag::DateTimeOriginal;
+
+/// The byte order of the TIFF structure holding EXIF data.
+///
+/// Canon CR2 files, for instance, are little-endian, while Nikon NEF files
+/// are big-endian.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+/// Rewrites nonstandard datetimes, such as `2023/05/01 12:00:00` or
+/// `2023-05-01T12:00:00`, in the `2023:05:01 12:00:00` EXIF format.
+fn normalize_datetime(ascii: &[u8]) -> Option<Vec<u8>> {
+    let date_separator = *ascii.get(4)?;
+    if !matches!(date_separator, b'/' | b'-') || ascii.get(7) != Some(&date_separator) {
+        return None;
+    }
+    if !matches!(ascii.get(10)?, b'T' | b' ') {
+        return None;
+    }
+    let mut normalized = ascii.to_vec();
+    normalized[4] = b':';
+    normalized[7] = b':';
+    normalized[10] = b' ';
+    Some(normalized)
+}
+
+/// The white balance mode set when the photo was taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum WhiteBalance {
+    Auto,
+    Manual,
+}
+
+impl fmt::Display for WhiteBalance {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WhiteBalance::Auto => write!(f, "auto"),
+            WhiteBalance::Manual => write!(f, "manual"),
+        }
+    }
+}
+
+/// The color space of the image data.
+///
+/// EXIF only defines sRGB, and Adobe RGB is conventionally recorded as `2`;
+/// other color spaces, such as ProPhoto RGB, are usually `0xFFFF`, for
+/// uncalibrated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSpace {
+    Srgb,
+    AdobeRgb,
+    Unknown(u16),
+}
+
+impl fmt::Display for ColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorSpace::Srgb => write!(f, "srgb"),
+            ColorSpace::AdobeRgb => write!(f, "adobergb"),
+            ColorSpace::Unknown(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// The orientation of the photo, as displayed.
+///
+/// Orientations 5 to 8 rotate the image data by a quarter turn, and are
+/// assumed to turn the landscape frame of the sensor into a portrait.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    Landscape,
+    Portrait,
+    Unknown(u16),
+}
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Orientation::Landscape => write!(f, "landscape"),
+            Orientation::Portrait => write!(f, "portrait"),
+            Orientation::Unknown(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// The range of the UTC offsets in use, from -12:00 to +14:00.
+const MIN_OFFSET_SECONDS: i32 = -12 * 3600;
+const MAX_OFFSET_SECONDS: i32 = 14 * 3600;
+
 pub struct Image {
     exif: exif::Exif,
 }
@@ -41,23 +307,114 @@ impl Image {
     }
 
     pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let img_file = File::open(path)?;
-        let mut img_buff = io::BufReader::new(img_file);
-        let exif = exif::Reader::new().read_from_container(&mut img_buff)?;
+        Self::open_with_capacity(path, DEFAULT_BUFFER_CAPACITY)
+    }
+
+    /// Opens an image, reading it through a buffer of `capacity` bytes, or
+    /// entirely into memory if no larger than `IN_MEMORY_THRESHOLD`.
+    pub fn open_with_capacity<P: AsRef<Path>>(path: P, capacity: usize) -> Result<Self> {
+        let path = path.as_ref();
+        let mut img_file = open_file(path)?;
+        let size = img_file.metadata()?.len();
+        let exif = if size <= IN_MEMORY_THRESHOLD {
+            let mut img_bytes = Vec::with_capacity(size as usize);
+            img_file.read_to_end(&mut img_bytes)?;
+            exif::Reader::new().read_from_container(&mut io::Cursor::new(img_bytes))
+        } else {
+            let mut img_buff = io::BufReader::with_capacity(capacity, img_file);
+            exif::Reader::new().read_from_container(&mut img_buff)
+        };
+        let exif = exif.map_err(|source| read_error(path, size, source))?;
+        Ok(Self::new(exif))
+    }
+
+    /// Opens an image, reading it through a memory map.
+    #[cfg(feature = "mmap")]
+    pub fn open_mmap<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let img_file = open_file(path)?;
+        // SAFETY: the map is dropped once parsed, but the file being truncated
+        // by another process in the meantime is not guarded against.
+        let img_mmap = unsafe { memmap2::Mmap::map(&img_file)? };
+        let exif = exif::Reader::new()
+            .read_from_container(&mut io::Cursor::new(&img_mmap[..]))
+            .map_err(|source| read_error(path, img_mmap.len() as u64, source))?;
         Ok(Self::new(exif))
     }
 
+    pub fn byte_order(&self) -> ByteOrder {
+        if self.exif.little_endian() {
+            ByteOrder::LittleEndian
+        } else {
+            ByteOrder::BigEndian
+        }
+    }
+
+    /// Looks up `tag` in the primary IFD, then in the thumbnail IFD some
+    /// JPEG files only store it in, then in any other IFD, as some Canon CR2
+    /// files only store it alongside their main image. Outside of the
+    /// primary IFD, EXIF tags may be stored in the IFD itself rather than in
+    /// its EXIF IFD, so the TIFF and EXIF contexts are not told apart. Other
+    /// contexts are, as GPS and interoperability tags reuse the same numbers.
     fn get_exif_field(&self, tag: exif::Tag) -> Result<&exif::Field> {
+        let has_tag = |field: &&exif::Field| {
+            field.tag.number() == tag.number()
+                && match (field.tag.context(), tag.context()) {
+                    (
+                        exif::Context::Tiff | exif::Context::Exif,
+                        exif::Context::Tiff | exif::Context::Exif,
+                    ) => true,
+                    (context, tag_context) => context == tag_context,
+                }
+        };
         self.exif
             .get_field(tag, exif::In::PRIMARY)
+            .or_else(|| {
+                self.exif
+                    .fields()
+                    .filter(has_tag)
+                    .find(|field| field.ifd_num == exif::In::THUMBNAIL)
+            })
+            .or_else(|| self.exif.fields().find(has_tag))
             .ok_or(Error::Tag(TagError::Missing))
     }
 
+    fn get_exif_string(&self, tag: exif::Tag) -> Result<String> {
+        let field = self.get_exif_field(tag)?;
+        match field.value {
+            exif::Value::Ascii(ref ascii) if !ascii.is_empty() => {
+                Ok(String::from_utf8_lossy(&ascii[0]).trim_end().to_string())
+            }
+            _ => Err(Error::Tag(TagError::Invalid)),
+        }
+    }
+
+    pub fn get_software(&self) -> Result<String> {
+        self.get_exif_string(exif::Tag::Software)
+    }
+
+    pub fn get_subsec(&self) -> Result<String> {
+        self.get_exif_string(exif::Tag::SubSecTimeOriginal)
+    }
+
+    /// Returns the raw bytes of the maker note, whose format is specific to
+    /// each camera maker, or `None` if there is none.
+    pub fn get_maker_note(&self) -> Option<&[u8]> {
+        match self.get_exif_field(exif::Tag::MakerNote).ok()?.value {
+            exif::Value::Undefined(ref bytes, _) => Some(bytes),
+            _ => None,
+        }
+    }
+
     fn get_exif_datetime_with(&self, tag: exif::Tag) -> Result<exif::DateTime> {
         let field = self.get_exif_field(tag)?;
         match field.value {
             exif::Value::Ascii(ref ascii) if !ascii.is_empty() => {
-                exif::DateTime::from_ascii(&ascii[0]).map_err(Error::Exif)
+                exif::DateTime::from_ascii(&ascii[0]).or_else(|err| {
+                    normalize_datetime(&ascii[0])
+                        .and_then(|ascii| exif::DateTime::from_ascii(&ascii).ok())
+                        .ok_or(Error::Exif(err))
+                })
             }
             _ => Err(Error::Tag(TagError::Invalid)),
         }
@@ -65,25 +422,487 @@ impl Image {
 
     fn get_naive_datetime_with(&self, tag: exif::Tag) -> Result<NaiveDateTime> {
         let edt = self.get_exif_datetime_with(tag)?;
-        let date = NaiveDate::from_ymd_opt(edt.year.into(), edt.month.into(), edt.day.into())
-            .ok_or(Error::OutOfRange)?;
-        date.and_hms_opt(edt.hour.into(), edt.minute.into(), edt.second.into())
-            .ok_or(Error::OutOfRange)
+        NaiveDate::from_ymd_opt(edt.year.into(), edt.month.into(), edt.day.into())
+            .and_then(|date| {
+                date.and_hms_opt(edt.hour.into(), edt.minute.into(), edt.second.into())
+            })
+            .ok_or(Error::OutOfRange {
+                year: edt.year,
+                month: edt.month,
+                day: edt.day,
+                hour: edt.hour,
+                minute: edt.minute,
+                second: edt.second,
+            })
     }
 
+    /// Returns the `DateTimeOriginal` tag, without any time zone applied.
+    ///
+    /// ```no_run
+    /// use namexif::image::Image;
+    ///
+    /// let image = Image::open("photo.jpg")?;
+    /// println!("{}", image.get_datetime_original_naive()?);
+    /// # Ok::<(), namexif::image::Error>(())
+    /// ```
+    pub fn get_datetime_original_naive(&self) -> Result<NaiveDateTime> {
+        self.get_naive_datetime_with(DATETIME_TAG)
+    }
+
+    #[deprecated(note = "renamed to `get_datetime_original_naive`")]
     pub fn get_naive_datetime(&self) -> Result<NaiveDateTime> {
-        self.get_naive_datetime_with(exif::Tag::DateTimeOriginal)
+        self.get_datetime_original_naive()
+    }
+
+    /// Returns whether the flash fired.
+    pub fn get_flash(&self) -> Result<bool> {
+        let flash = self
+            .get_exif_field(exif::Tag::Flash)?
+            .value
+            .get_uint(0)
+            .ok_or(Error::Tag(TagError::Invalid))?;
+        Ok(flash & 1 == 1)
+    }
+
+    pub fn get_white_balance(&self) -> Result<WhiteBalance> {
+        match self
+            .get_exif_field(exif::Tag::WhiteBalance)?
+            .value
+            .get_uint(0)
+        {
+            Some(0) => Ok(WhiteBalance::Auto),
+            Some(1) => Ok(WhiteBalance::Manual),
+            _ => Err(Error::Tag(TagError::Invalid)),
+        }
+    }
+
+    pub fn get_color_space(&self) -> Result<ColorSpace> {
+        let color_space = self
+            .get_exif_field(exif::Tag::ColorSpace)?
+            .value
+            .get_uint(0)
+            .and_then(|value| u16::try_from(value).ok())
+            .ok_or(Error::Tag(TagError::Invalid))?;
+        Ok(match color_space {
+            1 => ColorSpace::Srgb,
+            2 => ColorSpace::AdobeRgb,
+            value => ColorSpace::Unknown(value),
+        })
+    }
+
+    pub fn get_orientation(&self) -> Result<Orientation> {
+        let orientation = self
+            .get_exif_field(exif::Tag::Orientation)?
+            .value
+            .get_uint(0)
+            .and_then(|value| u16::try_from(value).ok())
+            .ok_or(Error::Tag(TagError::Invalid))?;
+        Ok(match orientation {
+            1..=4 => Orientation::Landscape,
+            5..=8 => Orientation::Portrait,
+            value => Orientation::Unknown(value),
+        })
+    }
+
+    /// Returns the width and height of the image in pixels.
+    ///
+    /// The dimensions recorded by cameras in the EXIF IFD are preferred to
+    /// those of the TIFF image data.
+    pub fn get_pixel_dimensions(&self) -> Result<(u32, u32)> {
+        let get_dimension = |tag| {
+            self.exif
+                .get_field(tag, exif::In::PRIMARY)
+                .ok_or(Error::Tag(TagError::Missing))?
+                .value
+                .get_uint(0)
+                .ok_or(Error::Tag(TagError::Invalid))
+        };
+        get_dimension(exif::Tag::PixelXDimension)
+            .and_then(|width| Ok((width, get_dimension(exif::Tag::PixelYDimension)?)))
+            .or_else(|_| {
+                Ok((
+                    get_dimension(exif::Tag::ImageWidth)?,
+                    get_dimension(exif::Tag::ImageLength)?,
+                ))
+            })
+    }
+
+    /// Returns the GPS altitude in meters, negative below sea level.
+    pub fn get_gps_altitude(&self) -> Result<f64> {
+        let altitude = match self.get_exif_field(exif::Tag::GPSAltitude)?.value {
+            exif::Value::Rational(ref altitude)
+                if !altitude.is_empty() && altitude[0].denom != 0 =>
+            {
+                altitude[0].to_f64()
+            }
+            _ => return Err(Error::Tag(TagError::Invalid)),
+        };
+        let below_sea_level = match self.get_exif_field(exif::Tag::GPSAltitudeRef) {
+            Ok(field) => field.value.get_uint(0) == Some(1),
+            Err(_) => false,
+        };
+        Ok(if below_sea_level { -altitude } else { altitude })
+    }
+
+    fn get_gps_coordinate(
+        &self,
+        tag: exif::Tag,
+        ref_tag: exif::Tag,
+        negative_ref: &str,
+    ) -> Result<f64> {
+        let coordinate = match self.get_exif_field(tag)?.value {
+            exif::Value::Rational(ref dms)
+                if dms.len() == 3 && dms.iter().all(|value| value.denom != 0) =>
+            {
+                dms[0].to_f64() + dms[1].to_f64() / 60.0 + dms[2].to_f64() / 3600.0
+            }
+            _ => return Err(Error::Tag(TagError::Invalid)),
+        };
+        Ok(if self.get_exif_string(ref_tag)? == negative_ref {
+            -coordinate
+        } else {
+            coordinate
+        })
     }
 
+    /// Returns the GPS latitude and longitude in degrees, negative south of
+    /// the equator and west of the prime meridian.
+    pub fn get_gps_coordinates(&self) -> Result<(f64, f64)> {
+        Ok((
+            self.get_gps_coordinate(exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef, "S")?,
+            self.get_gps_coordinate(exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef, "W")?,
+        ))
+    }
+
+    /// Returns the UTC datetime of the GPS fix.
+    pub fn get_gps_datetime(&self) -> Result<NaiveDateTime> {
+        let date =
+            NaiveDate::parse_from_str(&self.get_exif_string(exif::Tag::GPSDateStamp)?, "%Y:%m:%d")
+                .map_err(|_| Error::Tag(TagError::Invalid))?;
+        let field = self.get_exif_field(exif::Tag::GPSTimeStamp)?;
+        let seconds = match field.value {
+            exif::Value::Rational(ref hms)
+                if hms.len() == 3 && hms.iter().all(|part| part.denom != 0) =>
+            {
+                hms[0].to_f64() * 3600.0 + hms[1].to_f64() * 60.0 + hms[2].to_f64()
+            }
+            _ => return Err(Error::Tag(TagError::Invalid)),
+        };
+        let time = NaiveTime::from_num_seconds_from_midnight_opt(seconds as u32, 0)
+            .ok_or(Error::Tag(TagError::Invalid))?;
+        Ok(date.and_time(time))
+    }
+
+    /// Infers the UTC offset the photo was taken at, from the difference
+    /// between its local original datetime and its GPS datetime, rounded to
+    /// 15 minutes.
+    pub fn get_gps_offset(&self) -> Result<FixedOffset> {
+        const ROUNDING: f64 = 15.0 * 60.0;
+        let delta = self
+            .get_datetime_original_naive()?
+            .signed_duration_since(self.get_gps_datetime()?)
+            .num_seconds();
+        let offset = (delta as f64 / ROUNDING).round() * ROUNDING;
+        // Clocks which were never set, or GPS fixes from another day, would
+        // otherwise yield offsets no time zone uses.
+        if !(MIN_OFFSET_SECONDS as f64..=MAX_OFFSET_SECONDS as f64).contains(&offset) {
+            return Err(Error::Date(DateError::InvalidOffset));
+        }
+        FixedOffset::east_opt(offset as i32).ok_or(Error::Date(DateError::InvalidOffset))
+    }
+
+    /// Returns the original datetime, localized in `timezone`.
+    ///
+    /// A local time repeated when clocks fall back is resolved to standard
+    /// time, i.e. the instant with the smaller UTC offset.
+    ///
+    /// ```no_run
+    /// use namexif::image::Image;
+    ///
+    /// let image = Image::open("photo.jpg")?;
+    /// println!("{}", image.get_datetime(&chrono::Utc)?);
+    /// # Ok::<(), namexif::image::Error>(())
+    /// ```
     pub fn get_datetime<T>(&self, timezone: &T) -> Result<DateTime<T>>
     where
         T: TimeZone,
     {
-        let naive_datetime = self.get_naive_datetime()?;
-        match timezone.from_local_datetime(&naive_datetime) {
-            LocalResult::None => Err(Error::Date(DateError::InvalidLocalDatetime)),
-            LocalResult::Single(datetime) => Ok(datetime),
-            LocalResult::Ambiguous(..) => Err(Error::Date(DateError::AmbiguousLocalDatetime)),
+        localize(&self.get_datetime_original_naive()?, timezone)
+    }
+
+    /// Returns the UTC offset the original datetime was recorded at, from the
+    /// `OffsetTimeOriginal` tag, e.g. `+02:00`.
+    pub fn get_offset_time_original(&self) -> Result<FixedOffset> {
+        self.get_exif_string(exif::Tag::OffsetTimeOriginal)?
+            .trim()
+            .parse()
+            .map_err(|_| Error::Date(DateError::InvalidOffset))
+    }
+
+    /// Returns the original datetime at the UTC offset recorded along with
+    /// it, rather than in a caller-supplied time zone.
+    pub fn get_datetime_with_offset(&self) -> Result<DateTime<FixedOffset>> {
+        let offset = self.get_offset_time_original()?;
+        localize(&self.get_datetime_original_naive()?, &offset)
+    }
+}
+
+/// Localizes a naive datetime in `timezone`, resolving a local time repeated
+/// when clocks fall back to standard time.
+pub fn localize<T>(naive_datetime: &NaiveDateTime, timezone: &T) -> Result<DateTime<T>>
+where
+    T: TimeZone,
+{
+    match timezone.from_local_datetime(naive_datetime) {
+        LocalResult::None => Err(Error::Date(DateError::InvalidLocalDatetime)),
+        LocalResult::Single(datetime) => Ok(datetime),
+        LocalResult::Ambiguous(earliest, latest) => {
+            if latest.offset().fix().local_minus_utc() < earliest.offset().fix().local_minus_utc() {
+                Ok(latest)
+            } else {
+                Ok(earliest)
+            }
+        }
+    }
+}
+
+impl TryFrom<&Path> for Image {
+    type Error = Error;
+
+    fn try_from(path: &Path) -> Result<Self> {
+        Self::open(path)
+    }
+}
+
+impl TryFrom<PathBuf> for Image {
+    type Error = Error;
+
+    fn try_from(path: PathBuf) -> Result<Self> {
+        Self::open(path)
+    }
+}
+
+impl AsRef<exif::Exif> for Image {
+    fn as_ref(&self) -> &exif::Exif {
+        &self.exif
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exif::{In, Tag};
+
+    use super::*;
+    use crate::fixtures::{jpeg, TempDir, Tiff, Value};
+
+    fn open(name: &str, tiff: Tiff) -> Image {
+        let dir = TempDir::new();
+        Image::open(dir.write(name, tiff.to_jpeg())).unwrap()
+    }
+
+    /// Builds a TIFF file laid out like a Canon CR2 file, with the original
+    /// datetime stored in the IFD of the main image, as an IFD0 tag.
+    fn cr2() -> Tiff {
+        Tiff::new()
+            .little_endian()
+            .field(In::PRIMARY, Tag::Make, Value::ascii("Canon"))
+            .field(In::THUMBNAIL, Tag::ImageWidth, Value::Long(vec![160]))
+            .field(
+                In(2),
+                Tag(exif::Context::Tiff, Tag::DateTimeOriginal.number()),
+                Value::ascii("2024:01:02 03:04:05"),
+            )
+    }
+
+    fn naive(datetime: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(datetime, "%Y:%m:%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn reads_files_larger_than_in_memory_threshold_through_buffer() {
+        let mut exif = b"Exif\0\0".to_vec();
+        exif.extend(
+            Tiff::new()
+                .date_time_original("2024:01:02 03:04:05")
+                .to_bytes(),
+        );
+        let padding = vec![0; 60 * 1024];
+        let bytes = jpeg(&[&exif, &padding, &padding]);
+        assert!(bytes.len() as u64 > IN_MEMORY_THRESHOLD);
+        let dir = TempDir::new();
+        let image = Image::open_with_capacity(dir.write("a.jpg", bytes), 16).unwrap();
+        assert_eq!(
+            image.get_datetime_original_naive().unwrap(),
+            naive("2024:01:02 03:04:05")
+        );
+    }
+
+    #[test]
+    fn reads_datetime_from_thumbnail_ifd() {
+        let image = open(
+            "a.jpg",
+            Tiff::new()
+                .field(In::PRIMARY, Tag::Software, Value::ascii("scanner"))
+                .field(
+                    In::THUMBNAIL,
+                    Tag::DateTimeOriginal,
+                    Value::ascii("2024:01:02 03:04:05"),
+                ),
+        );
+        assert_eq!(
+            image.get_datetime_original_naive().unwrap(),
+            naive("2024:01:02 03:04:05")
+        );
+    }
+
+    #[test]
+    fn prefers_datetime_from_primary_ifd() {
+        let image = open(
+            "a.jpg",
+            Tiff::new().date_time_original("2024:01:02 03:04:05").field(
+                In::THUMBNAIL,
+                Tag::DateTimeOriginal,
+                Value::ascii("2020:01:01 00:00:00"),
+            ),
+        );
+        assert_eq!(
+            image.get_datetime_original_naive().unwrap(),
+            naive("2024:01:02 03:04:05")
+        );
+    }
+
+    #[test]
+    fn reads_datetime_when_primary_ifd_is_empty() {
+        let tiff = Tiff::new().field(
+            In::THUMBNAIL,
+            Tag::DateTimeOriginal,
+            Value::ascii("2024:01:02 03:04:05"),
+        );
+        let image = open("a.jpg", tiff);
+        assert!(image
+            .exif
+            .fields()
+            .all(|field| field.ifd_num == In::THUMBNAIL));
+        assert_eq!(
+            image.get_datetime_original_naive().unwrap(),
+            naive("2024:01:02 03:04:05")
+        );
+        assert!(matches!(
+            image.get_software(),
+            Err(Error::Tag(TagError::Missing))
+        ));
+    }
+
+    #[test]
+    fn reads_datetime_from_cr2_main_image_ifd() {
+        let dir = TempDir::new();
+        let image = Image::open(dir.write("IMG_0001.CR2", cr2().to_bytes())).unwrap();
+        assert_eq!(
+            image.get_datetime_original_naive().unwrap(),
+            naive("2024:01:02 03:04:05")
+        );
+    }
+
+    #[test]
+    fn reports_byte_order() {
+        let dir = TempDir::new();
+        let image = Image::open(dir.write("IMG_0001.CR2", cr2().to_bytes())).unwrap();
+        assert_eq!(image.byte_order(), ByteOrder::LittleEndian);
+        let image = open(
+            "a.jpg",
+            Tiff::new().date_time_original("2024:01:02 03:04:05"),
+        );
+        assert_eq!(image.byte_order(), ByteOrder::BigEndian);
+    }
+
+    fn gps(datetime: &str, gps_date: &str, gps_time: [(u32, u32); 3]) -> Tiff {
+        Tiff::new()
+            .date_time_original(datetime)
+            .field(In::PRIMARY, Tag::GPSDateStamp, Value::ascii(gps_date))
+            .field(
+                In::PRIMARY,
+                Tag::GPSTimeStamp,
+                Value::Rational(gps_time.to_vec()),
+            )
+    }
+
+    #[test]
+    fn infers_offset_from_gps_time() {
+        let image = open(
+            "a.jpg",
+            gps(
+                "2024:01:02 14:04:05",
+                "2024:01:02",
+                [(12, 1), (4, 1), (500, 100)],
+            ),
+        );
+        assert_eq!(
+            image.get_gps_datetime().unwrap(),
+            naive("2024:01:02 12:04:05")
+        );
+        assert_eq!(
+            image.get_gps_offset().unwrap(),
+            FixedOffset::east_opt(2 * 3600).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_gps_time_with_zero_denominator() {
+        let image = open(
+            "a.jpg",
+            gps(
+                "2024:01:02 14:04:05",
+                "2024:01:02",
+                [(12, 1), (4, 0), (5, 1)],
+            ),
+        );
+        assert!(matches!(
+            image.get_gps_datetime(),
+            Err(Error::Tag(TagError::Invalid))
+        ));
+        assert!(image.get_gps_offset().is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_gps_offsets() {
+        let cases = [
+            ("2024:01:02 14:00:00", "2024:01:03", Ok(-10)),
+            ("2024:01:02 12:00:00", "2024:01:03", Ok(-12)),
+            ("2024:01:02 11:00:00", "2024:01:03", Err(())),
+            ("2024:01:03 14:00:00", "2024:01:03", Ok(14)),
+            ("2024:01:03 15:00:00", "2024:01:03", Err(())),
+        ];
+        for (datetime, gps_date, expected) in cases {
+            let image = open("a.jpg", gps(datetime, gps_date, [(0, 1), (0, 1), (0, 1)]));
+            let offset = image
+                .get_gps_offset()
+                .map(|offset| offset.local_minus_utc() / 3600)
+                .map_err(|err| assert!(matches!(err, Error::Date(DateError::InvalidOffset))));
+            assert_eq!(offset, expected, "{}", datetime);
         }
     }
+
+    #[test]
+    fn does_not_mistake_interoperability_tags_for_gps_tags() {
+        let latitude = Value::Rational(vec![(48, 1), (51, 1), (0, 1)]);
+        let tiff = Tiff::new()
+            .field(In::PRIMARY, Tag::InteroperabilityIndex, Value::ascii("R98"))
+            .field(In::THUMBNAIL, Tag::GPSLatitude, latitude.clone())
+            .field(In::THUMBNAIL, Tag::GPSLongitude, latitude.clone());
+        let image = open("a.jpg", tiff);
+        assert!(matches!(
+            image.get_gps_coordinates(),
+            Err(Error::Tag(TagError::Missing))
+        ));
+
+        let tiff = Tiff::new()
+            .field(In::PRIMARY, Tag::InteroperabilityIndex, Value::ascii("R98"))
+            .field(In::THUMBNAIL, Tag::GPSLatitudeRef, Value::ascii("S"))
+            .field(In::THUMBNAIL, Tag::GPSLatitude, latitude.clone())
+            .field(In::THUMBNAIL, Tag::GPSLongitudeRef, Value::ascii("E"))
+            .field(In::THUMBNAIL, Tag::GPSLongitude, latitude);
+        let (latitude, longitude) = open("a.jpg", tiff).get_gps_coordinates().unwrap();
+        assert!((latitude + 48.85).abs() < 1e-9);
+        assert!((longitude - 48.85).abs() < 1e-9);
+    }
 }
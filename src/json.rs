@@ -0,0 +1,42 @@
+use std::io;
+
+fn write_string<W>(writer: &mut W, value: &str) -> io::Result<()>
+where
+    W: io::Write,
+{
+    write!(writer, "\"")?;
+    for c in value.chars() {
+        match c {
+            '"' => write!(writer, "\\\"")?,
+            '\\' => write!(writer, "\\\\")?,
+            '\n' => write!(writer, "\\n")?,
+            '\r' => write!(writer, "\\r")?,
+            '\t' => write!(writer, "\\t")?,
+            c if c < ' ' => write!(writer, "\\u{:04x}", u32::from(c))?,
+            c => write!(writer, "{}", c)?,
+        }
+    }
+    write!(writer, "\"")
+}
+
+/// Writes a JSON object of string members on a single line, as in JSON Lines.
+///
+/// Members with a `None` value are written as `null`.
+pub fn write_line<W>(writer: &mut W, members: &[(&str, Option<&str>)]) -> io::Result<()>
+where
+    W: io::Write,
+{
+    write!(writer, "{{")?;
+    for (i, (name, value)) in members.iter().enumerate() {
+        if i > 0 {
+            write!(writer, ",")?;
+        }
+        write_string(writer, name)?;
+        write!(writer, ":")?;
+        match value {
+            Some(value) => write_string(writer, value)?,
+            None => write!(writer, "null")?,
+        }
+    }
+    writeln!(writer, "}}")
+}
@@ -0,0 +1,10 @@
+pub mod bucket;
+pub mod csv;
+pub mod fileops;
+#[cfg(test)]
+mod fixtures;
+pub mod image;
+pub mod json;
+pub mod rename;
+pub mod template;
+pub mod xmp;
@@ -1,46 +1,377 @@
-mod image;
-mod rename;
-
 use std::{
+    collections::HashSet,
     fmt, fs,
     io::{self, Write},
     path::{Component, Path, PathBuf, MAIN_SEPARATOR},
-    process, result,
+    process, result, sync,
 };
 
 use chrono_tz::Tz;
 use derive_more::{Error, From};
+use namexif::{bucket, csv, fileops, image, json, rename, template};
 
 #[derive(Debug, clap::Parser)]
-#[clap(about)]
+#[clap(about, args_conflicts_with_subcommands = true)]
 pub struct Args {
-    /// Does not prompt for confirmation
-    #[clap(short = 'y', long = "assume-yes")]
-    pub assume_yes: bool,
-    /// Does not actually rename files
-    #[clap(short = 'n', long = "dry-run")]
-    pub dry_run: bool,
+    #[clap(subcommand)]
+    command: Option<Command>,
+    /// Options of the default rename command
+    #[clap(flatten)]
+    rename: RenameArgs,
+    /// Generate the completion script for the specified shell.
+    #[clap(long, exclusive = true, name = "SHELL")]
+    completion: Option<clap_complete::Shell>,
+    /// Set the verbosity level for log messages.
+    #[clap(global = true, long, default_value = "info", env = "NAMEXIF_LOG_LEVEL")]
+    log_level: tracing::level_filters::LevelFilter,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Renames photos according to their EXIF date tag (default)
+    Rename(Box<RenameArgs>),
+    /// Lists files without a usable EXIF date
+    Report(Box<PlanArgs>),
+    /// Compares the renames planned with two configurations
+    Diff(Box<DiffArgs>),
+    /// Prints the EXIF fields of a photo
+    DumpExif(DumpExifArgs),
+}
+
+/// Options deciding how files are named.
+#[derive(Debug, clap::Args)]
+pub struct PlanArgs {
     /// Filename format
     #[clap(
         short = 'f',
         long = "format",
         value_name = "format",
         env = "NAMEXIF_FORMAT",
-        default_value = "%Y-%m-%dT%H:%M:%S%z"
+        default_value = rename::DEFAULT_NAME_FORMAT
+    )]
+    pub name_format: template::Template,
+    /// Date and time separators replacing `-` and `:` in the default
+    /// filename format, e.g. `-.`
+    #[clap(
+        long = "separators",
+        value_name = "separators",
+        allow_hyphen_values = true,
+        conflicts_with = "name_format"
     )]
-    pub name_format: String,
+    pub separators: Option<Separators>,
+    /// Reads the date of files without an EXIF date from their XMP metadata
+    #[clap(long = "use-xmp")]
+    pub use_xmp: bool,
+    /// Reads the date of files without an EXIF date from their `.xmp`
+    /// sidecar
+    #[clap(long = "exif-fallback-xmp")]
+    pub exif_fallback_xmp: bool,
+    /// Filename format for files without an EXIF date, rendered against their
+    /// modification time
+    #[clap(long = "template-if-no-date", value_name = "format")]
+    pub template_if_no_date: Option<template::Template>,
+    /// Skips files whose EXIF software tag matches this glob pattern
+    #[clap(long = "skip-software-pattern", value_name = "pattern")]
+    pub skip_software_pattern: Option<glob::Pattern>,
+    /// Skips files whose flash did not fire, or fired
+    #[clap(long = "filter-flash", value_enum)]
+    pub filter_flash: Option<rename::FlashFilter>,
+    /// Skips files taken with another white balance mode
+    #[clap(long = "filter-white-balance", value_enum)]
+    pub filter_white_balance: Option<image::WhiteBalance>,
+    /// Skips files in another color space
+    #[clap(long = "filter-color-space", value_enum)]
+    pub filter_color_space: Option<rename::ColorSpaceFilter>,
+    /// Skips files below this resolution, in either orientation, e.g.
+    /// `4000x3000`
+    #[clap(long = "filter-min-resolution", value_name = "WxH")]
+    pub filter_min_resolution: Option<rename::Resolution>,
+    /// Size in bytes of the buffer EXIF data is read through
+    #[clap(
+        long = "read-buffer",
+        value_name = "bytes",
+        default_value_t = image::DEFAULT_BUFFER_CAPACITY
+    )]
+    pub read_buffer: usize,
+    /// Moves files into date subdirectories
+    #[clap(long = "bucket-by", value_enum)]
+    pub bucket_by: Option<bucket::BucketBy>,
+    /// Only renames files whose name does not already contain a date
+    #[clap(long = "only-undated")]
+    pub only_undated: bool,
+    /// Names files by the time elapsed since this reference datetime, e.g.
+    /// `2024-05-12T09:00:00+02:00`, instead of with the filename format
+    #[clap(long = "relative-to", value_name = "datetime")]
+    pub relative_to: Option<chrono::DateTime<chrono::FixedOffset>>,
+    /// Only renames files whose name does not already start with their date
+    #[clap(long = "rename-only-if-different-day")]
+    pub rename_only_if_different_day: bool,
+    /// Reports targets existing on disk as conflicts, along with the targets
+    /// wanted by several files, instead of skipping them
+    #[clap(long = "dereference-on-conflict", conflicts_with = "overwrite")]
+    pub dereference_on_conflict: bool,
+    /// Renames files onto existing files instead of skipping them
+    #[clap(long = "overwrite")]
+    pub overwrite: bool,
+    /// Decides which renames onto existing files are skipped, comparing the
+    /// EXIF dates of both files
+    #[clap(
+        long = "on-existing",
+        value_name = "policy",
+        value_enum,
+        default_value_t,
+        conflicts_with_all = ["overwrite", "dereference_on_conflict"]
+    )]
+    pub on_existing: rename::OnExisting,
+    /// Keeps the original extension instead of canonicalizing it, e.g. to
+    /// `jpg`
+    #[clap(long = "no-extension-canonicalization")]
+    pub no_extension_canonicalization: bool,
+    /// Case of the target name
+    #[clap(long = "case", value_enum, default_value_t)]
+    pub case: rename::Case,
+    /// Infers the UTC offset of photos from their GPS date and time,
+    /// falling back to the time zone
+    #[clap(long = "infer-offset")]
+    pub infer_offset: bool,
+    /// Decides between the UTC offset recorded in photos and the one of the
+    /// time zone, when they differ
+    #[clap(
+        long = "offset-conflict",
+        value_name = "policy",
+        value_enum,
+        default_value_t
+    )]
+    pub offset_conflict: rename::OffsetConflict,
+    /// Localizes photos in the time zone of their GPS coordinates, falling
+    /// back to the time zone (requires the timezone-auto feature)
+    #[clap(long = "timezone-auto")]
+    pub timezone_auto: bool,
     /// Time zone
     #[clap(short = 'z', long = "timezone", env = "NAMEXIF_TIMEZONE")]
     pub timezone: Option<Tz>,
-    /// Generate the completion script for the specified shell.
-    #[clap(long, exclusive = true, name = "SHELL")]
-    completion: Option<clap_complete::Shell>,
+    /// Skips files another process holds an advisory lock on (Unix only)
+    #[clap(long = "check-locks")]
+    pub check_locks: bool,
+    /// Skips files dated on a day cameras reset their clock to, as listed by
+    /// --bogus-date
+    #[clap(long = "skip-bogus-dates")]
+    pub skip_bogus_dates: bool,
+    /// Day cameras reset their clock to
+    #[clap(
+        long = "bogus-date",
+        value_name = "date",
+        default_values = ["1970-01-01", "1980-01-01"]
+    )]
+    pub bogus_dates: Vec<chrono::NaiveDate>,
+    /// Skips files smaller than this size in bytes
+    #[clap(long = "min-size", value_name = "bytes")]
+    pub min_size: Option<u64>,
+    /// Skips files processed by previous runs, and records processed files,
+    /// in this file
+    #[clap(long = "state", value_name = "path")]
+    pub state: Option<PathBuf>,
+    /// Reads input files from a list, one path per line
+    #[clap(
+        long = "input-list",
+        value_name = "path",
+        conflicts_with = "source_path"
+    )]
+    pub input_list: Option<PathBuf>,
     /// Input file or directory
     #[clap(value_name = "input", default_value = ".")]
     pub source_path: PathBuf,
-    /// Set the verbosity level for log messages.
-    #[clap(global = true, long, default_value = "info", env = "NAMEXIF_LOG_LEVEL")]
-    log_level: tracing::level_filters::LevelFilter,
+}
+
+/// The separators of the default filename format, written as a date
+/// separator followed by a time separator.
+#[derive(Clone, Copy, Debug)]
+pub struct Separators {
+    date: char,
+    time: char,
+}
+
+impl std::str::FromStr for Separators {
+    type Err = String;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next(), chars.next()) {
+            (Some(date), Some(time), None) => {
+                if [date, time]
+                    .iter()
+                    .any(|&c| c == '/' || c == MAIN_SEPARATOR)
+                {
+                    return Err("Separators cannot be path separators".to_string());
+                }
+                Ok(Self { date, time })
+            }
+            _ => Err("Expected a date separator followed by a time separator".to_string()),
+        }
+    }
+}
+
+impl Separators {
+    /// Returns the default filename format with these separators.
+    fn format(&self) -> template::Template {
+        let escape = |c: char| match c {
+            '%' => "%%".to_string(),
+            '{' => "{{".to_string(),
+            '}' => "}}".to_string(),
+            c => c.to_string(),
+        };
+        let date = escape(self.date);
+        let time = escape(self.time);
+        format!("%Y{date}%m{date}%dT%H{time}%M{time}%S%z")
+            .parse()
+            .unwrap()
+    }
+}
+
+#[derive(Debug, clap::Args)]
+pub struct RenameArgs {
+    /// Does not prompt for confirmation
+    #[clap(short = 'y', long = "assume-yes")]
+    pub assume_yes: bool,
+    /// Prints the names the filename format gives to sample datetimes, and
+    /// exits
+    #[clap(long = "preview")]
+    pub preview: bool,
+    /// Does not actually rename files
+    #[clap(short = 'n', long = "dry-run")]
+    pub dry_run: bool,
+    /// Creates hard links to files instead of moving them
+    #[clap(long = "hardlink")]
+    pub hardlink: bool,
+    /// Format of the plan
+    #[clap(long = "output", value_enum, default_value_t)]
+    pub output: Output,
+    /// Files listed in the plan
+    #[clap(long = "show", value_enum, default_value_t)]
+    pub show: Show,
+    /// Only lists the first entries of the plan, and how many were left out
+    #[clap(long = "preview-count", value_name = "count")]
+    pub preview_count: Option<usize>,
+    /// Displays paths relative to this directory, instead of to the common
+    /// ancestor of each source and target
+    #[clap(long = "display-relative-to", alias = "anchor", value_name = "dir")]
+    pub display_relative_to: Option<PathBuf>,
+    /// Shows which date source each target name was rendered from, besides
+    /// XMP sources, which are always shown
+    #[clap(long = "show-tag")]
+    pub show_tag: bool,
+    /// Restores the modification time of files after renaming them
+    #[clap(long = "preserve-timestamps")]
+    pub preserve_timestamps: bool,
+    /// Appends a CSV record of every successful rename to this file
+    #[clap(long = "csv-log", value_name = "path")]
+    pub csv_log: Option<PathBuf>,
+    /// Aborts once this many files failed to be read (0 for no limit)
+    #[clap(long = "max-errors", value_name = "count", default_value_t = 0)]
+    pub max_errors: usize,
+    /// Writes the plan to this file, to be applied later with --apply-plan
+    #[clap(long = "save-plan", value_name = "path", requires = "dry_run")]
+    pub save_plan: Option<PathBuf>,
+    /// Applies a plan written with --save-plan instead of reading EXIF data
+    #[clap(long = "apply-plan", value_name = "path", conflicts_with = "save_plan")]
+    pub apply_plan: Option<PathBuf>,
+    /// Moves the files overwritten by renames to the trash, with --overwrite
+    /// or --on-existing keep-older or keep-newer
+    #[clap(long = "trash")]
+    pub trash: bool,
+    /// Retries renames failing with transient errors this many times
+    #[clap(long = "retries", value_name = "count", default_value_t = 0)]
+    pub retries: u32,
+    /// Logs a single line per skip reason instead of one per skipped file
+    #[clap(long = "compact-skips")]
+    pub compact_skips: bool,
+    /// Only renames the earliest created of several files with the same
+    /// target, or the first by path, and skips the others
+    #[clap(long = "dedupe-keep-first")]
+    pub dedupe_keep_first: bool,
+    /// Exits with a bitmask of what happened: 1 if files could not be read
+    /// or renamed, 2 if there were conflicts, 4 if files were skipped, and 8
+    /// if the run was aborted
+    #[clap(long = "rich-exit")]
+    pub rich_exit: bool,
+    /// Skips conflicting files instead of aborting
+    #[clap(long = "skip-conflicts")]
+    pub skip_conflicts: bool,
+    /// Confirmation prompt
+    #[clap(long = "prompt", value_name = "message", default_value = "Proceed?")]
+    pub prompt: String,
+    /// Message printed when the answer to the prompt is not recognized
+    #[clap(
+        long = "prompt-invalid",
+        value_name = "message",
+        default_value = "Invalid input"
+    )]
+    pub prompt_invalid: String,
+    /// Comma-separated answers accepted as yes, case-insensitively
+    #[clap(
+        long = "yes-answers",
+        value_name = "answers",
+        value_delimiter = ',',
+        default_value = "y"
+    )]
+    pub yes_answers: Vec<String>,
+    /// Comma-separated answers accepted as no, case-insensitively
+    #[clap(
+        long = "no-answers",
+        value_name = "answers",
+        value_delimiter = ',',
+        default_value = "n"
+    )]
+    pub no_answers: Vec<String>,
+    #[clap(flatten)]
+    pub plan: PlanArgs,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Output {
+    /// Source and target paths, with their common parts factored out
+    #[default]
+    Plan,
+    /// A shell script of `mv` commands, only written if no rename conflicts
+    MvScript,
+    /// One JSON object per line, with the source and target paths, the
+    /// status and the skip or error message. Lines are streamed in no
+    /// particular order as files are read, and planned files skipped once
+    /// the whole plan is known, e.g. as their target exists, get another
+    /// line superseding the first
+    Jsonl,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Show {
+    /// Lists renamed and skipped files
+    All,
+    /// Lists renamed files
+    #[default]
+    Changes,
+    /// Lists no file
+    None,
+}
+
+/// Options of the diff command: plan A is made with the plan options, and
+/// plan B with the same options, besides those overridden.
+#[derive(Debug, clap::Args)]
+pub struct DiffArgs {
+    #[clap(flatten)]
+    pub plan: PlanArgs,
+    /// Filename format of plan B
+    #[clap(long = "b-format", value_name = "format")]
+    pub b_name_format: Option<template::Template>,
+    /// Time zone of plan B
+    #[clap(long = "b-timezone", value_name = "timezone")]
+    pub b_timezone: Option<Tz>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct DumpExifArgs {
+    /// Input file
+    #[clap(value_name = "input")]
+    pub source_path: PathBuf,
 }
 
 #[inline]
@@ -57,6 +388,9 @@ pub enum Error {
     Io(io::Error),
     #[error(ignore)]
     Conflicts(usize),
+    #[error(ignore)]
+    #[from(ignore)]
+    TooManyErrors(usize),
 }
 
 impl fmt::Display for Error {
@@ -64,40 +398,195 @@ impl fmt::Display for Error {
         match self {
             Error::Io(err) => err.fmt(f),
             Error::Conflicts(n) => write!(f, "{} conflicting file{}", n, pluralize(*n)),
+            Error::TooManyErrors(n) => write!(f, "Aborting after {} error{}", n, pluralize(*n)),
         }
     }
 }
 
 pub type Result<T> = result::Result<T, Error>;
 
+/// The strings of a confirmation prompt.
+struct Prompt<'a> {
+    message: &'a str,
+    invalid_input: &'a str,
+    yes_answers: &'a [String],
+    no_answers: &'a [String],
+}
+
+impl<'a> Prompt<'a> {
+    fn from_args(args: &'a RenameArgs) -> Self {
+        Self {
+            message: &args.prompt,
+            invalid_input: &args.prompt_invalid,
+            yes_answers: &args.yes_answers,
+            no_answers: &args.no_answers,
+        }
+    }
+
+    /// Returns the hint listing the first yes and no answers, the default
+    /// one in uppercase, e.g. `yN` or `oui/NON`.
+    fn hint(&self, default: bool) -> String {
+        let yes = self.yes_answers.first().map_or("", String::as_str);
+        let no = self.no_answers.first().map_or("", String::as_str);
+        let separator = if yes.chars().count() > 1 || no.chars().count() > 1 {
+            "/"
+        } else {
+            ""
+        };
+        if default {
+            format!("{}{}{}", yes.to_uppercase(), separator, no.to_lowercase())
+        } else {
+            format!("{}{}{}", yes.to_lowercase(), separator, no.to_uppercase())
+        }
+    }
+
+    fn answer(&self, input: &str) -> Option<bool> {
+        let matches = |answers: &[String]| {
+            answers
+                .iter()
+                .any(|answer| answer.to_lowercase() == input.to_lowercase())
+        };
+        if matches(self.yes_answers) {
+            Some(true)
+        } else if matches(self.no_answers) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/// Asks for confirmation on stderr, so that plans written to stdout, e.g. as
+/// shell scripts, are left as they are.
 fn prompt_confirm(
     stdin: &io::Stdin,
-    stdout: &mut io::Stdout,
-    message: &str,
+    stderr: &mut io::Stderr,
+    prompt: &Prompt,
     default: bool,
 ) -> io::Result<bool> {
     let mut input = String::new();
     loop {
-        print!("{} [{}] ", message, if default { "Yn" } else { "yN" });
-        stdout.flush()?;
+        eprint!("{} [{}] ", prompt.message, prompt.hint(default));
+        stderr.flush()?;
         stdin.read_line(&mut input)?;
         {
             let input = input.trim_end();
-            match input {
-                "" => return Ok(default),
-                "y" | "Y" => return Ok(true),
-                "n" | "N" => return Ok(false),
-                _ => eprintln!("Invalid input: {}", input),
+            if input.is_empty() {
+                return Ok(default);
+            }
+            match prompt.answer(input) {
+                Some(answer) => return Ok(answer),
+                None => eprintln!("{}: {}", prompt.invalid_input, input),
             }
         }
         input.clear();
     }
 }
 
-pub fn get_renames(args: &Args) -> io::Result<rename::Renames> {
+fn get_name_format(args: &PlanArgs) -> template::Template {
+    match args.separators {
+        Some(separators) => separators.format(),
+        None => args.name_format.clone(),
+    }
+}
+
+fn get_rename_options(args: &PlanArgs) -> io::Result<rename::Options> {
+    let processed = match &args.state {
+        Some(state_path) => rename::read_state(state_path)?,
+        None => Default::default(),
+    };
+    if args.timezone_auto && !cfg!(feature = "timezone-auto") {
+        tracing::warn!(
+            "Ignoring --timezone-auto, as namexif was built without the timezone-auto feature"
+        );
+    }
+    Ok(rename::Options {
+        name_format: get_name_format(args),
+        template_if_no_date: args.template_if_no_date.clone(),
+        case: args.case,
+        filter_flash: args.filter_flash,
+        filter_white_balance: args.filter_white_balance,
+        filter_color_space: args.filter_color_space,
+        filter_min_resolution: args.filter_min_resolution,
+        skip_software_pattern: args.skip_software_pattern.clone(),
+        read_buffer: args.read_buffer,
+        bucket_by: args.bucket_by,
+        only_undated: args.only_undated,
+        infer_offset: args.infer_offset,
+        offset_conflict: args.offset_conflict,
+        // Only renames abort on errors.
+        max_errors: 0,
+        keep_extension: args.no_extension_canonicalization,
+        relative_to: args.relative_to,
+        overwrite: args.overwrite,
+        // Only renames link files.
+        hardlink: false,
+        only_if_different_day: args.rename_only_if_different_day,
+        use_xmp: args.use_xmp,
+        use_xmp_sidecar: args.exif_fallback_xmp,
+        processed,
+        check_locks: args.check_locks,
+        min_size: args.min_size,
+        bogus_dates: if args.skip_bogus_dates {
+            args.bogus_dates.clone()
+        } else {
+            Vec::new()
+        },
+        existing_as_conflicts: args.dereference_on_conflict,
+        on_existing: args.on_existing,
+        timezone_auto: args.timezone_auto,
+    })
+}
+
+pub fn get_renames(args: &PlanArgs, options: &rename::Options) -> io::Result<rename::Renames> {
+    get_renames_in(args, args.timezone, options)
+}
+
+/// Plans renames in `timezone`, or in the local time zone.
+fn get_renames_in(
+    args: &PlanArgs,
+    timezone: Option<Tz>,
+    options: &rename::Options,
+) -> io::Result<rename::Renames> {
+    if let Some(input_list) = &args.input_list {
+        let source_paths = rename::read_input_list(input_list)?;
+        return Ok(match timezone {
+            None => rename::get_renames_from(source_paths, &chrono::Local, options),
+            Some(timezone) => rename::get_renames_from(source_paths, &timezone, options),
+        });
+    }
+    match timezone {
+        None => rename::get_renames(&args.source_path, &chrono::Local, options),
+        Some(timezone) => rename::get_renames(&args.source_path, &timezone, options),
+    }
+}
+
+/// Like `get_renames`, but calls `on_entry` with each entry as soon as it is
+/// resolved.
+fn get_renames_streamed<E>(
+    args: &PlanArgs,
+    options: &rename::Options,
+    on_entry: E,
+) -> io::Result<rename::Renames>
+where
+    E: Fn(&Path, &result::Result<rename::Target, rename::Error>) + Sync,
+{
+    if let Some(input_list) = &args.input_list {
+        let source_paths = rename::read_input_list(input_list)?;
+        return Ok(match args.timezone {
+            None => {
+                rename::get_renames_from_streamed(source_paths, &chrono::Local, options, on_entry)
+            }
+            Some(timezone) => {
+                rename::get_renames_from_streamed(source_paths, &timezone, options, on_entry)
+            }
+        });
+    }
     match args.timezone {
-        None => rename::get_renames(&args.source_path, &chrono::Local, &args.name_format),
-        Some(timezone) => rename::get_renames(&args.source_path, &timezone, &args.name_format),
+        None => rename::get_renames_streamed(&args.source_path, &chrono::Local, options, on_entry),
+        Some(timezone) => {
+            rename::get_renames_streamed(&args.source_path, &timezone, options, on_entry)
+        }
     }
 }
 
@@ -107,14 +596,26 @@ pub fn common_ancestor<'a>(source_path: &'a Path, target_path: &'a Path) -> Opti
         .find(|&ancestor| target_path.starts_with(ancestor))
 }
 
-fn write_rename<W>(f: &mut W, source_path: &Path, target_path: &Path) -> io::Result<()>
+fn write_rename<W>(
+    f: &mut W,
+    source_path: &Path,
+    target_path: &Path,
+    date_source: Option<rename::DateSource>,
+    anchor: Option<&Path>,
+) -> io::Result<()>
 where
     W: io::Write,
 {
     let mut source_path = source_path;
     let mut target_path = target_path;
     let mut ancestor_empty = true;
-    if let Some(ancestor_path) = common_ancestor(source_path, target_path) {
+    let ancestor_path = match anchor {
+        Some(anchor) if source_path.starts_with(anchor) && target_path.starts_with(anchor) => {
+            Some(anchor)
+        }
+        _ => common_ancestor(source_path, target_path),
+    };
+    if let Some(ancestor_path) = ancestor_path {
         source_path = source_path.strip_prefix(ancestor_path).unwrap();
         target_path = target_path.strip_prefix(ancestor_path).unwrap();
         for component in ancestor_path.components() {
@@ -131,7 +632,7 @@ where
             }
         }
     }
-    writeln!(
+    write!(
         f,
         "{}{} => {}{}",
         if ancestor_empty { "" } else { "{" },
@@ -139,71 +640,533 @@ where
         target_path.display(),
         if ancestor_empty { "" } else { "}" },
     )?;
+    if let Some(date_source) = date_source {
+        write!(f, "  [{}]", date_source)?;
+    }
+    writeln!(f)?;
+    Ok(())
+}
+
+/// Quotes a path for POSIX shells, between single quotes.
+fn shell_quote(path: &Path) -> Vec<u8> {
+    let mut quoted = vec![b'\''];
+    for &byte in path.as_os_str().as_encoded_bytes() {
+        if byte == b'\'' {
+            quoted.extend_from_slice(b"'\\''");
+        } else {
+            quoted.push(byte);
+        }
+    }
+    quoted.push(b'\'');
+    quoted
+}
+
+fn write_mv_command<W>(f: &mut W, source_path: &Path, target_path: &Path) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let target_parent = target_path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty() && Some(*parent) != source_path.parent());
+    if let Some(target_parent) = target_parent {
+        f.write_all(b"mkdir -p -- ")?;
+        f.write_all(&shell_quote(target_parent))?;
+        f.write_all(b"\n")?;
+    }
+    f.write_all(b"mv -- ")?;
+    f.write_all(&shell_quote(source_path))?;
+    f.write_all(b" ")?;
+    f.write_all(&shell_quote(target_path))?;
+    f.write_all(b"\n")
+}
+
+fn write_json_line<W>(
+    f: &mut W,
+    source_path: &Path,
+    target: &result::Result<rename::Target, rename::Error>,
+) -> io::Result<()>
+where
+    W: io::Write,
+{
+    let source_path = source_path.to_string_lossy();
+    match target {
+        Ok(target) => json::write_line(
+            f,
+            &[
+                ("source", Some(&source_path)),
+                ("target", Some(&target.path.to_string_lossy())),
+                ("status", Some("planned")),
+                ("message", None),
+            ],
+        ),
+        Err(err) => {
+            let status = match err {
+                rename::Error::Skip(_) => "skipped",
+                rename::Error::Image(_) => "failed",
+            };
+            json::write_line(
+                f,
+                &[
+                    ("source", Some(&source_path)),
+                    ("target", None),
+                    ("status", Some(status)),
+                    ("message", Some(&err.to_string())),
+                ],
+            )
+        }
+    }
+}
+
+fn write_skip<W, E>(f: &mut W, source_path: &Path, err: E) -> io::Result<()>
+where
+    W: io::Write,
+    E: fmt::Display,
+{
+    writeln!(f, "{}  (skipped: {})", source_path.display(), err)
+}
+
+fn get_apply_options(args: &RenameArgs) -> rename::ApplyOptions {
+    rename::ApplyOptions {
+        hardlink: args.hardlink,
+        preserve_timestamps: args.preserve_timestamps,
+        trash: args.trash,
+        retries: args.retries,
+    }
+}
+
+fn open_csv_log(path: &Path) -> io::Result<fs::File> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    if file.metadata()?.len() == 0 {
+        csv::write_record(&mut file, &["timestamp", "source", "target", "datetime"])?;
+    }
+    Ok(file)
+}
+
+fn write_csv_log<W>(f: &mut W, source_path: &Path, target: &rename::Target) -> io::Result<()>
+where
+    W: io::Write,
+{
+    csv::write_record(
+        f,
+        &[
+            &chrono::Local::now().to_rfc3339(),
+            &source_path.to_string_lossy(),
+            &target.path.to_string_lossy(),
+            &target.datetime.to_rfc3339(),
+        ],
+    )?;
+    f.flush()
+}
+
+const RICH_EXIT_ERRORS: i32 = 1;
+const RICH_EXIT_CONFLICTS: i32 = 1 << 1;
+const RICH_EXIT_SKIPS: i32 = 1 << 2;
+const RICH_EXIT_ABORTED: i32 = 1 << 3;
+
+#[derive(Debug, Default)]
+struct Summary {
+    renamed: usize,
+    trashed: usize,
+    errors: usize,
+    skipped: usize,
+    skipped_conflicts: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} renamed file{}",
+            self.renamed,
+            pluralize(self.renamed)
+        )?;
+        if self.errors > 0 {
+            write!(f, ", {} error{}", self.errors, pluralize(self.errors))?;
+        }
+        if self.trashed > 0 {
+            write!(
+                f,
+                ", {} trashed file{}",
+                self.trashed,
+                pluralize(self.trashed)
+            )?;
+        }
+        if self.skipped_conflicts > 0 {
+            write!(
+                f,
+                ", {} skipped conflicting file{}",
+                self.skipped_conflicts,
+                pluralize(self.skipped_conflicts)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether the entry is listed in the plan.
+fn is_displayed<T, E>(args: &RenameArgs, target: &result::Result<T, E>) -> bool {
+    match (target, args.show) {
+        (_, Show::None) => false,
+        (Ok(_), _) => true,
+        // Shell scripts only list commands.
+        (Err(_), _) if args.output == Output::MvScript => false,
+        (Err(_), Show::All) => true,
+        (Err(_), Show::Changes) => false,
+    }
+}
+
+/// The JSON Lines written as files are read, before the plan is complete.
+#[derive(Debug, Default)]
+struct JsonlStream {
+    displayed: usize,
+    hidden: usize,
+    /// The sources written as planned, which get another line if they are
+    /// skipped once the plan is complete.
+    planned: HashSet<PathBuf>,
+    /// The error writing a line, if any, after which no more are written.
+    error: Option<io::Error>,
+}
+
+impl JsonlStream {
+    fn write(
+        &mut self,
+        args: &RenameArgs,
+        source_path: &Path,
+        target: &result::Result<rename::Target, rename::Error>,
+    ) {
+        if self.error.is_some() || !is_displayed(args, target) {
+            return;
+        }
+        if args
+            .preview_count
+            .is_some_and(|count| self.displayed >= count)
+        {
+            self.hidden += 1;
+            return;
+        }
+        self.displayed += 1;
+        match write_json_line(&mut io::stdout().lock(), source_path, target) {
+            Ok(()) if target.is_ok() => {
+                self.planned.insert(source_path.to_path_buf());
+            }
+            Ok(()) => {}
+            Err(err) => self.error = Some(err),
+        }
+    }
+}
+
+/// Logs the conflicts of the plan, failing if there are any.
+fn check_conflicts(renames: &rename::Renames) -> Result<()> {
+    let mut conflicts = 0;
+    for conflict in renames.conflicts() {
+        tracing::error!("{}", conflict);
+        conflicts += 1;
+    }
+    if conflicts > 0 {
+        return Err(Error::Conflicts(conflicts));
+    }
     Ok(())
 }
 
-fn try_run(args: &Args) -> Result<(usize, usize)> {
+/// Plans and applies renames, counting what happened in `summary` as it goes,
+/// so that it is complete up to the point the run was aborted at, if it was.
+fn try_rename(args: &RenameArgs, summary: &mut Summary) -> Result<()> {
     let stdin = io::stdin();
     let mut stdout = io::stdout();
-    let renames = get_renames(args)?;
+    let options = rename::Options {
+        max_errors: args.max_errors,
+        hardlink: args.hardlink,
+        ..get_rename_options(&args.plan)?
+    };
+    // Plans are streamed as files are read, not when read from plan files.
+    let streamed = args.output == Output::Jsonl && args.apply_plan.is_none();
+    let stream = sync::Mutex::new(JsonlStream::default());
+    let mut renames = match &args.apply_plan {
+        Some(plan_path) => rename::read_plan(plan_path, &options)?,
+        None if streamed => get_renames_streamed(&args.plan, &options, |source_path, target| {
+            stream.lock().unwrap().write(args, source_path, target);
+        })?,
+        None => get_renames(&args.plan, &options)?,
+    };
+    let mut stream = stream.into_inner().unwrap();
+    if let Some(err) = stream.error.take() {
+        return Err(err.into());
+    }
+
+    if args.dedupe_keep_first {
+        renames.skip_duplicates();
+    }
+
+    // Skip conflicting files up front, so that they are left out of the plan.
+    if args.skip_conflicts {
+        for conflict in renames.conflicts() {
+            tracing::warn!("{}", conflict);
+        }
+        summary.skipped_conflicts = renames.skip_conflicts();
+    }
 
     // Look for errors and retrieve paths.
-    let mut paths: Vec<(&Path, &Path)> = Vec::with_capacity(renames.len());
-    let mut errors = 0;
-    for (source_path, target_path) in renames.iter() {
-        match target_path {
+    let mut paths: Vec<(&Path, &rename::Target)> = Vec::with_capacity(renames.len());
+    let mut skip_counts: Vec<(rename::SkipError, usize)> = Vec::new();
+    for (index, (source_path, target)) in renames.iter().enumerate() {
+        match target {
+            Err(rename::Error::Skip(err @ rename::SkipError::AlreadyExists)) => {
+                tracing::warn!("Skipping file {}: {}", source_path.display(), err);
+            }
+            Err(rename::Error::Skip(err)) if args.compact_skips => {
+                match skip_counts.iter_mut().find(|(reason, _)| reason == err) {
+                    Some((_, count)) => *count += 1,
+                    None => skip_counts.push((err.clone(), 1)),
+                }
+            }
             Err(rename::Error::Skip(err)) => {
                 tracing::info!("Skipping file {}: {}", source_path.display(), err);
             }
+            Err(rename::Error::Image(err @ image::Error::FileLocked)) => {
+                tracing::warn!("Skipping file {}: {}", source_path.display(), err);
+            }
+            Err(rename::Error::Image(err)) if err.permission_denied() || err.not_found() => {
+                tracing::info!("Skipping file {}: {}", source_path.display(), err);
+            }
             Err(rename::Error::Image(err)) => {
                 tracing::error!("Skipping file {}: {}", source_path.display(), err);
-                errors += 1;
+                summary.errors += 1;
+                if args.max_errors > 0 && summary.errors >= args.max_errors {
+                    summary.skipped = index + 1 - paths.len() - summary.errors;
+                    return Err(Error::TooManyErrors(summary.errors));
+                }
             }
-            Ok(target_path) => {
-                paths.push((source_path, target_path));
+            Ok(target) => {
+                paths.push((source_path, target));
             }
         }
     }
+    summary.skipped = renames.len() - paths.len() - summary.errors;
+
+    for (reason, count) in skip_counts {
+        tracing::info!("Skipping {} file{}: {}", count, pluralize(count), reason);
+    }
+
+    // Shell scripts are run as printed, so they must not list conflicting
+    // commands.
+    if args.output == Output::MvScript {
+        check_conflicts(&renames)?;
+    }
 
     // Display paths.
-    for (source_path, target_path) in &paths {
-        write_rename(&mut stdout, source_path, target_path)?;
+    let mut displayed = 0;
+    let mut hidden = stream.hidden;
+    for (source_path, target) in renames.iter() {
+        if streamed {
+            // Supersede the lines of the planned files skipped since.
+            if target.is_err() && stream.planned.contains(source_path) {
+                write_json_line(&mut stdout, source_path, target)?;
+            }
+            continue;
+        }
+        if !is_displayed(args, target) {
+            continue;
+        }
+        if args.preview_count.is_some_and(|count| displayed >= count) {
+            hidden += 1;
+            continue;
+        }
+        displayed += 1;
+        if args.output == Output::Jsonl {
+            write_json_line(&mut stdout, source_path, target)?;
+            continue;
+        }
+        match target {
+            Ok(target) if args.output == Output::MvScript => {
+                write_mv_command(&mut stdout, source_path, &target.path)?;
+            }
+            Ok(target) => {
+                // Dates read from XMP are always annotated.
+                let date_source =
+                    (args.show_tag || target.date_source.is_xmp()).then_some(target.date_source);
+                write_rename(
+                    &mut stdout,
+                    source_path,
+                    &target.path,
+                    date_source,
+                    args.display_relative_to.as_deref(),
+                )?;
+            }
+            Err(err) => write_skip(&mut stdout, source_path, err)?,
+        }
+    }
+    if hidden > 0 {
+        // Keep shell scripts and JSON Lines valid.
+        match args.output {
+            Output::Plan => writeln!(stdout, "... and {} more", hidden)?,
+            Output::MvScript => writeln!(stdout, "# ... and {} more", hidden)?,
+            Output::Jsonl => tracing::info!("{} more entries not listed", hidden),
+        }
     }
 
-    // Look for conflicts.
-    let mut conflicts = 0;
-    for conflict in renames.conflicts() {
-        tracing::error!("{}", conflict);
-        conflicts += 1;
+    if let Some(plan_path) = &args.save_plan {
+        let mut plan_file = io::BufWriter::new(fs::File::create(plan_path)?);
+        renames.write_plan(&mut plan_file)?;
+        plan_file.flush()?;
     }
-    if conflicts > 0 {
-        return Err(Error::Conflicts(conflicts));
+
+    if args.output != Output::MvScript {
+        check_conflicts(&renames)?;
     }
 
     // Rename files.
-    let mut renamed = 0;
+    tracing::info!("{}", renames.pre_apply_stats());
     if !paths.is_empty()
         && !args.dry_run
-        && (args.assume_yes || prompt_confirm(&stdin, &mut stdout, "Proceed?", false)?)
+        && (args.assume_yes
+            || prompt_confirm(&stdin, &mut io::stderr(), &Prompt::from_args(args), false)?)
     {
-        for (source_path, target_path) in &paths {
-            match fs::rename(source_path, target_path) {
-                Err(err) => {
+        let mut csv_log = args.csv_log.as_deref().map(open_csv_log).transpose()?;
+        let mut renamed_paths = Vec::new();
+        let stats = rename::apply_plan_with(
+            &renames,
+            &fileops::StdFileOps,
+            &get_apply_options(args),
+            |outcome| match outcome {
+                rename::Outcome::Renamed {
+                    op,
+                    trashed: was_trashed,
+                } => {
+                    if was_trashed {
+                        summary.trashed += 1;
+                    }
+                    // Record both sides, so that neither is renamed again.
+                    renamed_paths.push(op.source);
+                    renamed_paths.push(op.target.path.as_path());
+                    if let Some(csv_log) = &mut csv_log {
+                        if let Err(err) = write_csv_log(csv_log, op.source, op.target) {
+                            tracing::error!("Can't write CSV log: {}", err);
+                        }
+                    }
+                }
+                rename::Outcome::Failed { op, error } => {
                     tracing::error!(
                         "Can't rename {} to {}: {}",
-                        source_path.display(),
-                        target_path.display(),
-                        err
+                        op.source.display(),
+                        op.target.path.display(),
+                        error
                     );
-                    errors += 1;
-                }
-                Ok(_) => {
-                    renamed += 1;
                 }
+            },
+        );
+        tracing::info!("{}", stats);
+        if let Some(state_path) = &args.plan.state {
+            // Files which failed or were skipped are left to later runs.
+            let processed = options.processed.iter().map(PathBuf::as_path);
+            rename::write_state(state_path, processed.chain(renamed_paths))?;
+        }
+        summary.renamed = stats.renamed;
+        summary.errors += stats.failed;
+    }
+    Ok(())
+}
+
+fn try_report_missing(args: &PlanArgs) -> Result<usize> {
+    let mut stdout = io::stdout();
+    let options = rename::Options {
+        template_if_no_date: None,
+        ..get_rename_options(args)?
+    };
+    let renames = get_renames(args, &options)?;
+    let mut missing = 0;
+    for (source_path, target) in renames.iter() {
+        if let Err(rename::Error::Image(err)) = target {
+            if err.is_unusable_datetime() {
+                writeln!(stdout, "{}", source_path.display())?;
+                missing += 1;
             }
         }
     }
-    Ok((renamed, errors))
+    Ok(missing)
+}
+
+fn report_missing(args: &PlanArgs) -> ! {
+    match try_report_missing(args) {
+        Ok(0) => {
+            tracing::info!("No file without a date");
+            process::exit(0);
+        }
+        Ok(missing) => {
+            tracing::info!("{} file{} without a date", missing, pluralize(missing));
+            process::exit(1);
+        }
+        Err(err) => {
+            tracing::error!("{}", err);
+            process::exit(2);
+        }
+    }
+}
+
+/// Prints how each file is renamed by either plan, returning how many files
+/// are not renamed the same way.
+fn try_diff(args: &DiffArgs) -> Result<usize> {
+    let mut stdout = io::stdout();
+    let options_a = get_rename_options(&args.plan)?;
+    let options_b = rename::Options {
+        name_format: match &args.b_name_format {
+            Some(name_format) => name_format.clone(),
+            None => options_a.name_format.clone(),
+        },
+        ..get_rename_options(&args.plan)?
+    };
+    let renames_a = get_renames(&args.plan, &options_a)?;
+    let renames_b = get_renames_in(
+        &args.plan,
+        args.b_timezone.or(args.plan.timezone),
+        &options_b,
+    )?;
+    let mut changes = 0;
+    for (source_path, diff) in renames_a.diff(&renames_b) {
+        let (target_a, target_b) = match diff {
+            rename::PlanDiff::Same(target) => (Some(target), Some(target)),
+            rename::PlanDiff::Different(target_a, target_b) => (Some(target_a), Some(target_b)),
+            rename::PlanDiff::OnlyInA(target_a) => (Some(target_a), None),
+            rename::PlanDiff::OnlyInB(target_b) => (None, Some(target_b)),
+        };
+        if !matches!(diff, rename::PlanDiff::Same(_)) {
+            changes += 1;
+        }
+        let display = |path: Option<&Path>| {
+            path.map(|path| path.display().to_string())
+                .unwrap_or_default()
+        };
+        writeln!(
+            stdout,
+            "{}\t{}\t{}\t{}",
+            diff,
+            source_path.display(),
+            display(target_a),
+            display(target_b)
+        )?;
+    }
+    Ok(changes)
+}
+
+/// Exits like `diff`: with 0 if the plans are the same, 1 if they differ and
+/// 2 on errors.
+fn diff(args: &DiffArgs) -> ! {
+    match try_diff(args) {
+        Ok(0) => {
+            tracing::info!("Both plans rename files the same way");
+            process::exit(0);
+        }
+        Ok(changes) => {
+            tracing::info!("{} file{} renamed differently", changes, pluralize(changes));
+            process::exit(1);
+        }
+        Err(err) => {
+            tracing::error!("{}", err);
+            process::exit(2);
+        }
+    }
 }
 
 fn generate_completions(shell: clap_complete::Shell) -> ! {
@@ -225,35 +1188,204 @@ fn setup_logging(log_level: tracing::level_filters::LevelFilter) -> anyhow::Resu
     Ok(())
 }
 
-fn main() -> anyhow::Result<()> {
-    let args = <Args as clap::Parser>::parse();
-    if let Some(shell) = args.completion {
-        generate_completions(shell);
+/// Encodes what happened in a bitmask, for --rich-exit.
+fn rich_exit_code(summary: &Summary, result: &Result<()>) -> i32 {
+    let mut code = 0;
+    if summary.errors > 0 {
+        code |= RICH_EXIT_ERRORS;
     }
-    setup_logging(args.log_level)?;
+    if summary.skipped_conflicts > 0 || matches!(result, Err(Error::Conflicts(_))) {
+        code |= RICH_EXIT_CONFLICTS;
+    }
+    if summary.skipped > 0 {
+        code |= RICH_EXIT_SKIPS;
+    }
+    if matches!(result, Err(err) if !matches!(err, Error::Conflicts(_))) {
+        code |= RICH_EXIT_ABORTED;
+    }
+    code
+}
+
+/// Local datetimes exercising the filename format: a leap day, times
+/// repeated when clocks fall back in Europe and in North America, and the
+/// last second of a year.
+const PREVIEW_DATETIMES: [&str; 4] = [
+    "2024-02-29T12:00:00",
+    "2024-10-27T02:30:00",
+    "2024-11-03T01:30:00",
+    "2024-12-31T23:59:59",
+];
 
-    match try_run(&args) {
-        Ok((0, 0)) => {
+fn write_preview<W, T>(f: &mut W, args: &PlanArgs, timezone: &T) -> io::Result<()>
+where
+    W: io::Write,
+    T: chrono::TimeZone,
+    T::Offset: fmt::Display,
+{
+    let fields = |field| match field {
+        template::Field::OriginalName => Some("IMG_0001".to_string()),
+        _ => None,
+    };
+    let name_format = get_name_format(args);
+    let now = chrono::Utc::now().with_timezone(timezone);
+    writeln!(f, "now => {}.jpg", name_format.render(&now, fields))?;
+    for naive_datetime in PREVIEW_DATETIMES {
+        let naive_datetime: chrono::NaiveDateTime = naive_datetime.parse().unwrap();
+        match image::localize(&naive_datetime, timezone) {
+            Ok(datetime) => writeln!(
+                f,
+                "{} => {}.jpg",
+                naive_datetime,
+                name_format.render(&datetime, fields)
+            )?,
+            Err(err) => writeln!(f, "{} => ({})", naive_datetime, err)?,
+        }
+    }
+    Ok(())
+}
+
+fn preview(args: &PlanArgs) -> ! {
+    let mut stdout = io::stdout().lock();
+    let result = match args.timezone {
+        None => write_preview(&mut stdout, args, &chrono::Local),
+        Some(timezone) => write_preview(&mut stdout, args, &timezone),
+    };
+    match result {
+        Ok(()) => process::exit(0),
+        Err(err) => {
+            tracing::error!("{}", err);
+            process::exit(2);
+        }
+    }
+}
+
+fn rename(args: &RenameArgs) -> ! {
+    if args.trash && !args.plan.overwrite && args.plan.on_existing == rename::OnExisting::Skip {
+        <Args as clap::CommandFactory>::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "--trash requires --overwrite or --on-existing keep-older or keep-newer",
+            )
+            .exit();
+    }
+    if args.preview {
+        preview(&args.plan);
+    }
+    let mut summary = Summary::default();
+    let result = try_rename(args, &mut summary);
+    let exit_code = match &result {
+        Ok(()) if summary.renamed == 0 && summary.errors == 0 && summary.skipped_conflicts == 0 => {
             tracing::info!("Nothing to do");
-            process::exit(0);
+            0
         }
-        Ok((renamed, 0)) => {
-            tracing::info!("{} renamed file{}", renamed, pluralize(renamed));
-            process::exit(0);
+        Ok(()) => {
+            tracing::info!("{}", summary);
+            if summary.errors > 0 {
+                1
+            } else {
+                0
+            }
         }
-        Ok((renamed, errors)) => {
-            tracing::info!(
-                "{} renamed file{}, {} error{}",
-                renamed,
-                pluralize(renamed),
-                errors,
-                pluralize(errors)
-            );
-            process::exit(1);
+        Err(err) => {
+            tracing::error!("{}", err);
+            2
         }
+    };
+    if args.rich_exit {
+        process::exit(rich_exit_code(&summary, &result));
+    }
+    process::exit(exit_code);
+}
+
+fn try_dump_exif(args: &DumpExifArgs) -> image::Result<()> {
+    let mut stdout = io::stdout();
+    let image = image::Image::open(&args.source_path)?;
+    let exif: &exif::Exif = image.as_ref();
+    for field in exif.fields() {
+        writeln!(
+            stdout,
+            "{} {}: {}",
+            field.ifd_num,
+            field.tag,
+            field.display_value().with_unit(exif)
+        )?;
+    }
+    Ok(())
+}
+
+fn dump_exif(args: &DumpExifArgs) -> ! {
+    match try_dump_exif(args) {
+        Ok(()) => process::exit(0),
         Err(err) => {
             tracing::error!("{}", err);
             process::exit(2);
         }
     }
 }
+
+fn main() -> anyhow::Result<()> {
+    let args = <Args as clap::Parser>::parse();
+    if let Some(shell) = args.completion {
+        generate_completions(shell);
+    }
+    setup_logging(args.log_level)?;
+
+    match &args.command {
+        None => rename(&args.rename),
+        Some(Command::Rename(rename_args)) => rename(rename_args),
+        Some(Command::Report(plan_args)) => report_missing(plan_args),
+        Some(Command::Diff(diff_args)) => diff(diff_args),
+        Some(Command::DumpExif(dump_exif_args)) => dump_exif(dump_exif_args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+
+    #[test]
+    fn csv_log_records_renames() {
+        let target = rename::Target {
+            path: PathBuf::from("photos/2024-01-02.jpg"),
+            datetime: DateTime::parse_from_rfc3339("2024-01-02T03:04:05+01:00").unwrap(),
+            date_source: rename::DateSource::Plan,
+            size: 0,
+        };
+        let mut log = Vec::new();
+        write_csv_log(&mut log, Path::new("a, b.jpg"), &target).unwrap();
+        let records = csv::read_records(str::from_utf8(&log).unwrap()).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            records[0][1..],
+            [
+                "a, b.jpg",
+                "photos/2024-01-02.jpg",
+                "2024-01-02T03:04:05+01:00"
+            ]
+        );
+    }
+
+    #[test]
+    fn rich_exit_code_is_additive() {
+        let summary = Summary {
+            errors: 1,
+            skipped: 2,
+            ..Summary::default()
+        };
+        assert_eq!(
+            rich_exit_code(&summary, &Ok(())),
+            RICH_EXIT_ERRORS | RICH_EXIT_SKIPS
+        );
+        assert_eq!(
+            rich_exit_code(&summary, &Err(Error::TooManyErrors(1))),
+            RICH_EXIT_ERRORS | RICH_EXIT_SKIPS | RICH_EXIT_ABORTED
+        );
+        assert_eq!(
+            rich_exit_code(&summary, &Err(Error::Conflicts(3))),
+            RICH_EXIT_ERRORS | RICH_EXIT_CONFLICTS | RICH_EXIT_SKIPS
+        );
+        assert_eq!(rich_exit_code(&Summary::default(), &Ok(())), 0);
+    }
+}
@@ -1,23 +1,45 @@
 use std::{
-    collections::{btree_map, hash_set},
+    collections::{self, btree_map, hash_set},
     error,
-    ffi::{OsStr, OsString},
+    ffi::OsString,
     fmt, fs, io,
-    path::{Path, PathBuf},
-    result,
+    path::{self, Path, PathBuf},
+    result, str,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+    time::{Duration, Instant, SystemTime},
+    vec,
 };
 
-use chrono::TimeZone;
+use chrono::{DateTime, FixedOffset, NaiveDate, Offset, TimeDelta, TimeZone, Utc};
 use derive_more::{Display, From};
-use rayon::iter::{FromParallelIterator, IntoParallelIterator, ParallelIterator};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
-use crate::image;
+use crate::{
+    bucket, csv,
+    fileops::{FileOps, StdFileOps},
+    image, template, xmp,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum SkipError {
     Directory,
     Extension,
     WellNamed,
+    Vanished,
+    FilterMismatch,
+    Conflict,
+    AlreadyDated,
+    NonUtf8Extension,
+    Filtered,
+    AlreadyExists,
+    AlreadyProcessed,
+    Duplicate,
+    TooSmall,
+    BelowMinResolution,
+    BogusDate,
+    /// Left unread as enough files already failed to be read.
+    TooManyErrors,
 }
 
 impl fmt::Display for SkipError {
@@ -26,6 +48,19 @@ impl fmt::Display for SkipError {
             SkipError::Directory => write!(f, "Is a directory"),
             SkipError::Extension => write!(f, "Not an EXIF file"),
             SkipError::WellNamed => write!(f, "Does not need renaming"),
+            SkipError::Vanished => write!(f, "No longer exists"),
+            SkipError::FilterMismatch => write!(f, "Does not match filters"),
+            SkipError::Conflict => write!(f, "Conflicts with another file"),
+            SkipError::AlreadyDated => write!(f, "Name already contains a date"),
+            SkipError::NonUtf8Extension => write!(f, "Extension is not valid UTF-8"),
+            SkipError::Filtered => write!(f, "Excluded by filter"),
+            SkipError::AlreadyExists => write!(f, "Target file already exists"),
+            SkipError::AlreadyProcessed => write!(f, "Processed by a previous run"),
+            SkipError::Duplicate => write!(f, "Same target as an earlier file"),
+            SkipError::TooSmall => write!(f, "Smaller than the minimum size"),
+            SkipError::BelowMinResolution => write!(f, "Below the minimum resolution"),
+            SkipError::BogusDate => write!(f, "Date is a camera default"),
+            SkipError::TooManyErrors => write!(f, "Not read after too many errors"),
         }
     }
 }
@@ -40,18 +75,45 @@ pub enum Error {
 
 impl error::Error for Error {}
 
+impl Error {
+    /// Whether the file failed to be read, rather than being left out: files
+    /// which are locked, which can't be accessed or which vanished are
+    /// skipped.
+    pub fn is_failure(&self) -> bool {
+        match self {
+            Error::Image(image::Error::FileLocked) => false,
+            Error::Image(err) => !err.permission_denied() && !err.not_found(),
+            Error::Skip(_) => false,
+        }
+    }
+}
+
 type Result<T> = result::Result<T, Error>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Side {
     Source,
     Target,
 }
 
+impl fmt::Display for Side {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Side::Source => write!(f, "source"),
+            Side::Target => write!(f, "target"),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Conflict<'a> {
     pub side: Side,
     pub path: &'a Path,
+    /// The source of the rename the conflict was found at.
+    pub source_path: &'a Path,
+    /// The source of the earlier rename targeting `path`, or `None` if
+    /// `path` is a file that already exists on disk.
+    pub other_source_path: Option<&'a Path>,
 }
 
 impl<'a> error::Error for Conflict<'a> {}
@@ -60,44 +122,114 @@ impl<'a> fmt::Display for Conflict<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "{} file {} is overwritten",
-            match self.side {
-                Side::Source => "Source",
-                Side::Target => "Target",
-            },
-            self.path.display(),
-        )
+            "Conflict on {} file {}: ",
+            self.side,
+            self.path.display()
+        )?;
+        match (self.side, self.other_source_path) {
+            (_, None) => write!(f, "already exists"),
+            (Side::Source, Some(other_source_path)) => {
+                write!(f, "overwritten by {}", other_source_path.display())
+            }
+            (Side::Target, Some(other_source_path)) => write!(
+                f,
+                "wanted by both {} and {}",
+                other_source_path.display(),
+                self.source_path.display(),
+            ),
+        }
     }
 }
 
-pub struct Conflicts<'a> {
-    items: btree_map::Iter<'a, PathBuf, Result<PathBuf>>,
-    target_paths: hash_set::HashSet<&'a Path>,
+#[derive(Clone, Copy, Debug)]
+pub enum DateSource {
+    Tag(exif::Tag),
+    /// The XMP packet embedded in the file.
+    Xmp,
+    /// The `.xmp` sidecar of the file.
+    XmpSidecar,
+    Mtime,
+    /// Read back from a saved plan.
+    Plan,
 }
 
-impl<'a> Iterator for Conflicts<'a> {
+impl DateSource {
+    /// Whether the date was read from XMP metadata rather than EXIF tags.
+    pub fn is_xmp(self) -> bool {
+        matches!(self, DateSource::Xmp | DateSource::XmpSidecar)
+    }
+}
+
+impl fmt::Display for DateSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DateSource::Tag(tag) => tag.fmt(f),
+            DateSource::Xmp => write!(f, "xmp"),
+            DateSource::XmpSidecar => write!(f, "xmp sidecar"),
+            DateSource::Mtime => write!(f, "mtime"),
+            DateSource::Plan => write!(f, "plan"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Target {
+    pub path: PathBuf,
+    /// The datetime the target name was rendered from.
+    pub datetime: DateTime<FixedOffset>,
+    pub date_source: DateSource,
+    /// The size of the source file in bytes, when the plan was made.
+    pub size: u64,
+}
+
+/// Detects conflicts in the order renames are applied.
+struct UnsortedConflicts<'a> {
+    items: btree_map::Iter<'a, PathBuf, Result<Target>>,
+    /// The targets seen so far, along with the first source claiming them.
+    target_paths: collections::HashMap<&'a Path, &'a Path>,
+    /// The sources of the planned renames, if targets existing on disk are
+    /// reported unless moved away.
+    moved: Option<hash_set::HashSet<&'a Path>>,
+}
+
+impl<'a> Iterator for UnsortedConflicts<'a> {
     type Item = Conflict<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
-            let (source_path, target_path) = self.items.next()?;
-            if let Ok(target_path) = target_path {
+            let (source_path, target) = self.items.next()?;
+            if let Ok(target) = target {
                 let source_path = source_path.as_ref();
-                let target_path = target_path.as_ref();
-                let conflict = if self.target_paths.contains(source_path) {
+                let target_path = target.path.as_ref();
+                let conflict = if let Some(other_source_path) = self.target_paths.get(source_path) {
                     Some(Conflict {
                         side: Side::Source,
                         path: source_path,
+                        source_path,
+                        other_source_path: Some(other_source_path),
                     })
-                } else if self.target_paths.contains(target_path) {
+                } else if let Some(other_source_path) = self.target_paths.get(target_path) {
                     Some(Conflict {
                         side: Side::Target,
                         path: target_path,
+                        source_path,
+                        other_source_path: Some(other_source_path),
                     })
                 } else {
-                    None
+                    self.moved
+                        .as_ref()
+                        .filter(|moved| {
+                            !moved.contains(target_path)
+                                && fs::symlink_metadata(target_path).is_ok()
+                        })
+                        .map(|_| Conflict {
+                            side: Side::Target,
+                            path: target_path,
+                            source_path,
+                            other_source_path: None,
+                        })
                 };
-                self.target_paths.insert(target_path);
+                self.target_paths.entry(target_path).or_insert(source_path);
                 if conflict.is_some() {
                     return conflict;
                 }
@@ -106,57 +238,1220 @@ impl<'a> Iterator for Conflicts<'a> {
     }
 }
 
+pub struct Conflicts<'a> {
+    conflicts: vec::IntoIter<Conflict<'a>>,
+}
+
+impl<'a> Iterator for Conflicts<'a> {
+    type Item = Conflict<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.conflicts.next()
+    }
+}
+
+/// Statistics about a plan, before it is applied.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RenameStats {
+    pub planned: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+impl fmt::Display for RenameStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} planned, {} skipped, {} failed",
+            self.planned, self.skipped, self.failed
+        )
+    }
+}
+
+/// Statistics about an applied plan.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PostApplyStats {
+    pub renamed: usize,
+    pub failed: usize,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for PostApplyStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} renamed, {} failed in {:.3}s",
+            self.renamed,
+            self.failed,
+            self.elapsed.as_secs_f64()
+        )
+    }
+}
+
+/// Plan entries, along with their source paths.
+pub type Entries<T> = Vec<(PathBuf, T)>;
+
+/// How a file is renamed by two plans, e.g. made with different options.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlanDiff<'a> {
+    /// Both plans rename the file to this target.
+    Same(&'a Path),
+    /// The plans rename the file to these different targets.
+    Different(&'a Path, &'a Path),
+    /// Only the first plan renames the file, to this target.
+    OnlyInA(&'a Path),
+    /// Only the second plan renames the file, to this target.
+    OnlyInB(&'a Path),
+}
+
+impl<'a> fmt::Display for PlanDiff<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlanDiff::Same(_) => write!(f, "SAME"),
+            PlanDiff::Different(..) => write!(f, "DIFFERENT"),
+            PlanDiff::OnlyInA(_) => write!(f, "ONLY_IN_A"),
+            PlanDiff::OnlyInB(_) => write!(f, "ONLY_IN_B"),
+        }
+    }
+}
+
+/// Decides whether the rename of `source_path` overwrites the existing file
+/// at its target, by comparing their original datetimes, and logs why.
+///
+/// Existing files without a readable date are kept.
+fn keeps_over_existing(source_path: &Path, target: &Target, on_existing: OnExisting) -> bool {
+    if on_existing == OnExisting::Skip {
+        return false;
+    }
+    let existing_datetime = match image::Image::open(&target.path)
+        .and_then(|image| image.get_datetime_original_naive())
+    {
+        Ok(existing_datetime) => existing_datetime,
+        Err(err) => {
+            tracing::info!(
+                "Keeping existing file {}, as its date can't be read: {}",
+                target.path.display(),
+                err
+            );
+            return false;
+        }
+    };
+    let datetime = target.datetime.naive_local();
+    let keeps = match on_existing {
+        OnExisting::Skip => false,
+        OnExisting::KeepOlder => datetime < existing_datetime,
+        OnExisting::KeepNewer => datetime > existing_datetime,
+    };
+    let (kept, kept_datetime, other, other_datetime) = if keeps {
+        (
+            source_path,
+            datetime,
+            target.path.as_path(),
+            existing_datetime,
+        )
+    } else {
+        (
+            target.path.as_path(),
+            existing_datetime,
+            source_path,
+            datetime,
+        )
+    };
+    tracing::info!(
+        "Keeping {} dated {} over {} dated {}",
+        kept.display(),
+        kept_datetime,
+        other.display(),
+        other_datetime
+    );
+    keeps
+}
+
+/// How renames targeting files existing on disk are handled.
+#[derive(Clone, Copy, Debug)]
+enum ExistingTargets {
+    /// They are planned, overwriting the existing files.
+    Overwritten,
+    /// They are reported as conflicts.
+    Conflicting,
+    /// They are skipped, unless kept over the existing files.
+    Skipped(OnExisting),
+}
+
 pub struct Renames {
-    items: btree_map::BTreeMap<PathBuf, Result<PathBuf>>,
+    items: btree_map::BTreeMap<PathBuf, Result<Target>>,
+    existing: ExistingTargets,
+    /// Whether sources are left in place, as hard links are created to them.
+    keeps_sources: bool,
+    /// The sources kept over the existing files at their target, so that
+    /// each is only compared once.
+    kept_over_existing: hash_set::HashSet<PathBuf>,
 }
 
 impl Renames {
-    pub fn conflicts(&self) -> Conflicts {
-        Conflicts {
+    fn new(options: &Options) -> Self {
+        let existing = if options.overwrite {
+            ExistingTargets::Overwritten
+        } else if options.existing_as_conflicts {
+            ExistingTargets::Conflicting
+        } else {
+            ExistingTargets::Skipped(options.on_existing)
+        };
+        Self {
+            items: btree_map::BTreeMap::new(),
+            existing,
+            keeps_sources: options.hardlink,
+            kept_over_existing: hash_set::HashSet::new(),
+        }
+    }
+
+    /// Returns the sources of the planned renames, which are moved away,
+    /// unless they are hard linked.
+    fn moved(&self) -> hash_set::HashSet<&Path> {
+        self.items
+            .iter()
+            .filter(|(_, target)| !self.keeps_sources && target.is_ok())
+            .map(|(source_path, _)| source_path.as_path())
+            .collect()
+    }
+
+    /// Returns the conflicts of the plan, sorted by contested path, then by
+    /// source path.
+    pub fn conflicts(&self) -> Conflicts<'_> {
+        let moved = matches!(self.existing, ExistingTargets::Conflicting).then(|| self.moved());
+        let mut conflicts: Vec<_> = UnsortedConflicts {
             items: self.iter(),
-            target_paths: hash_set::HashSet::with_capacity(self.items.len()),
+            target_paths: collections::HashMap::with_capacity(self.items.len()),
+            moved,
+        }
+        .collect();
+        // Renames are iterated by source path, and the sort is stable.
+        conflicts.sort_by_key(|conflict| conflict.path);
+        Conflicts {
+            conflicts: conflicts.into_iter(),
         }
     }
 
-    pub fn iter(&self) -> btree_map::Iter<PathBuf, Result<PathBuf>> {
+    /// Skips the renames targeting the same path as an earlier rename,
+    /// returning how many were skipped.
+    ///
+    /// Renames are ordered by the creation time of their source, which keeps
+    /// the first of several photos taken within the same second when the
+    /// target name has no subsecond digits, then by source path, on platforms
+    /// and filesystems which do not record creation times. Renames without a
+    /// creation time come after the others. `ctime` is not used, as it
+    /// changes whenever a file is renamed.
+    ///
+    /// Skipped renames leave their source in place, so renames onto them are
+    /// skipped in turn, unless existing targets are overwritten or reported
+    /// as conflicts.
+    pub fn skip_duplicates(&mut self) -> usize {
+        self.skip_duplicates_by(|source_path| {
+            fs::metadata(source_path)
+                .and_then(|metadata| metadata.created())
+                .ok()
+        })
+    }
+
+    /// Like `skip_duplicates`, with the creation times `created` returns.
+    fn skip_duplicates_by<F>(&mut self, created: F) -> usize
+    where
+        F: Fn(&Path) -> Option<SystemTime>,
+    {
+        let mut planned: Vec<_> = self
+            .items
+            .iter()
+            .filter_map(|(source_path, target)| {
+                let target = target.as_ref().ok()?;
+                let created = created(source_path);
+                Some((
+                    created.is_none(),
+                    created,
+                    source_path.clone(),
+                    target.path.clone(),
+                ))
+            })
+            .collect();
+        planned.sort();
+        let mut target_paths = hash_set::HashSet::new();
+        let mut skipped = 0;
+        for (_, _, source_path, target_path) in planned {
+            if !target_paths.insert(target_path) {
+                self.items
+                    .insert(source_path, Err(Error::Skip(SkipError::Duplicate)));
+                skipped += 1;
+            }
+        }
+        self.skip_existing();
+        skipped
+    }
+
+    /// Skips the renames involved in conflicts, returning how many were skipped.
+    ///
+    /// Skipping a rename leaves its source in place, so renames targeting it
+    /// are skipped in turn, until no conflict is left, as are renames onto
+    /// files existing on disk which are no longer moved away.
+    pub fn skip_conflicts(&mut self) -> usize {
+        let mut skipped = 0;
+        loop {
+            let mut conflicting: hash_set::HashSet<PathBuf> = self
+                .conflicts()
+                .map(|conflict| conflict.path.to_path_buf())
+                .collect();
+            conflicting.extend(
+                self.items
+                    .iter()
+                    .filter(|(_, target)| target.is_err())
+                    .map(|(source_path, _)| source_path.clone()),
+            );
+            let mut newly_skipped = 0;
+            for (source_path, target) in self.items.iter_mut() {
+                let is_conflicting = match target {
+                    Ok(target) => {
+                        conflicting.contains(source_path) || conflicting.contains(&target.path)
+                    }
+                    Err(_) => false,
+                };
+                if is_conflicting {
+                    *target = Err(Error::Skip(SkipError::Conflict));
+                    newly_skipped += 1;
+                }
+            }
+            if newly_skipped == 0 {
+                self.skip_existing();
+                return skipped;
+            }
+            skipped += newly_skipped;
+        }
+    }
+
+    /// Inserts the entry for `source_path`, or replaces the existing one with
+    /// an error if the same source was listed twice.
+    fn insert_or_error(&mut self, source_path: PathBuf, target: Result<Target>) {
+        match self.items.entry(source_path) {
+            btree_map::Entry::Vacant(entry) => {
+                entry.insert(target);
+            }
+            btree_map::Entry::Occupied(mut entry) => {
+                *entry.get_mut() = Err(image::Error::Io(io::Error::new(
+                    io::ErrorKind::AlreadyExists,
+                    "duplicate source path",
+                ))
+                .into());
+            }
+        }
+    }
+
+    /// Returns the total size in bytes of the files planned to be renamed.
+    pub fn total_bytes(&self) -> u64 {
+        self.items
+            .values()
+            .filter_map(|target| target.as_ref().ok())
+            .map(|target| target.size)
+            .sum()
+    }
+
+    pub fn pre_apply_stats(&self) -> RenameStats {
+        let mut stats = RenameStats::default();
+        for target in self.items.values() {
+            match target {
+                Ok(_) => stats.planned += 1,
+                Err(Error::Skip(_)) => stats.skipped += 1,
+                Err(Error::Image(_)) => stats.failed += 1,
+            }
+        }
+        stats
+    }
+
+    /// Removes the entry for `source_path`, returning it if it was planned.
+    ///
+    /// Like a skipped entry, a removed one leaves its source in place, so
+    /// renames onto it are skipped or reported as conflicts, unless existing
+    /// targets are overwritten.
+    pub fn remove(&mut self, source_path: &Path) -> Option<Result<Target>> {
+        let target = self.items.remove(source_path);
+        self.skip_existing();
+        target
+    }
+
+    /// Keeps only the entries for which `f` returns `true`.
+    ///
+    /// Removed entries leave their source in place, as with `remove`.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&Path, &Result<Target>) -> bool,
+    {
+        self.items
+            .retain(|source_path, target| f(source_path, target));
+        self.skip_existing();
+    }
+
+    /// Skips the renames targeting files that exist on disk, if they are
+    /// skipped rather than overwritten or reported as conflicts, unless they
+    /// are moved away by another rename of the plan, or kept over the
+    /// existing files by `on_existing`.
+    ///
+    /// Skipping a rename leaves its source in place, so renames targeting it
+    /// are skipped in turn. This is run again whenever entries are skipped,
+    /// as their sources are then no longer moved away.
+    fn skip_existing(&mut self) {
+        let on_existing = match self.existing {
+            ExistingTargets::Skipped(on_existing) => on_existing,
+            _ => return,
+        };
+        loop {
+            let moved: hash_set::HashSet<PathBuf> =
+                self.moved().into_iter().map(Path::to_path_buf).collect();
+            let mut newly_skipped = 0;
+            for (source_path, target) in self.items.iter_mut() {
+                let skipped = match target {
+                    Ok(target)
+                        if moved.contains(&target.path)
+                            || self.kept_over_existing.contains(source_path)
+                            || fs::symlink_metadata(&target.path).is_err() =>
+                    {
+                        false
+                    }
+                    Ok(target) => {
+                        let keeps = keeps_over_existing(source_path, target, on_existing);
+                        if keeps {
+                            self.kept_over_existing.insert(source_path.clone());
+                        }
+                        !keeps
+                    }
+                    Err(_) => false,
+                };
+                if skipped {
+                    *target = Err(Error::Skip(SkipError::AlreadyExists));
+                    newly_skipped += 1;
+                }
+            }
+            if newly_skipped == 0 {
+                return;
+            }
+        }
+    }
+
+    /// Splits the plan into planned renames and failures.
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    ///
+    /// use namexif::rename::{get_renames, Options};
+    ///
+    /// let renames = get_renames(Path::new("."), &chrono::Local, &Options::default())?;
+    /// let (planned, failed) = renames.partition();
+    /// for (source_path, target) in planned {
+    ///     println!("{} => {}", source_path.display(), target.path.display());
+    /// }
+    /// println!("{} failures", failed.len());
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn partition(self) -> (Entries<Target>, Entries<Error>) {
+        let mut planned = Vec::new();
+        let mut failed = Vec::new();
+        for (source_path, target) in self.items {
+            match target {
+                Ok(target) => planned.push((source_path, target)),
+                Err(err) => failed.push((source_path, err)),
+            }
+        }
+        (planned, failed)
+    }
+
+    /// Splits the plan into planned renames, skipped files and files that
+    /// could not be read.
+    ///
+    /// ```no_run
+    /// use std::path::Path;
+    ///
+    /// use namexif::rename::{get_renames, Options};
+    ///
+    /// let renames = get_renames(Path::new("."), &chrono::Local, &Options::default())?;
+    /// let (_, _, unreadable) = renames.partition_by_error_kind();
+    /// for (source_path, err) in unreadable {
+    ///     eprintln!("{}: {}", source_path.display(), err);
+    /// }
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn partition_by_error_kind(
+        self,
+    ) -> (Entries<Target>, Entries<SkipError>, Entries<image::Error>) {
+        let (planned, failed) = self.partition();
+        let mut skipped = Vec::new();
+        let mut unreadable = Vec::new();
+        for (source_path, err) in failed {
+            match err {
+                Error::Skip(err) => skipped.push((source_path, err)),
+                Error::Image(err) => unreadable.push((source_path, err)),
+            }
+        }
+        (planned, skipped, unreadable)
+    }
+
+    /// Writes the planned renames as CSV, to be applied later with
+    /// `read_plan`.
+    pub fn write_plan<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        csv::write_record(writer, &PLAN_HEADER)?;
+        for (source_path, target) in self.iter() {
+            if let Ok(target) = target {
+                csv::write_record(
+                    writer,
+                    &[
+                        &source_path.to_string_lossy(),
+                        &target.path.to_string_lossy(),
+                        &target.datetime.to_rfc3339(),
+                    ],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes every entry of the plan as CSV, with its status and, for
+    /// skipped and failed files, the reason why.
+    pub fn to_csv<W>(&self, writer: &mut W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        csv::write_record(writer, &["source", "target", "status", "message"])?;
+        for (source_path, target) in self.iter() {
+            let source_path = source_path.to_string_lossy();
+            match target {
+                Ok(target) => csv::write_record(
+                    writer,
+                    &[&source_path, &target.path.to_string_lossy(), "planned", ""],
+                )?,
+                Err(err) => {
+                    let status = match err {
+                        Error::Skip(_) => "skipped",
+                        Error::Image(_) => "failed",
+                    };
+                    csv::write_record(writer, &[&source_path, "", status, &err.to_string()])?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the planned renames, skipping the entries that were skipped or
+    /// failed.
+    pub fn iter_ops(&self) -> impl Iterator<Item = RenameOp<'_>> {
+        self.iter().filter_map(|(source_path, target)| {
+            target.as_ref().ok().map(|target| RenameOp {
+                source: source_path,
+                target,
+            })
+        })
+    }
+
+    /// Compares the planned renames with those of `other`, by source path.
+    ///
+    /// Files renamed by neither plan are left out.
+    pub fn diff<'a>(&'a self, other: &'a Renames) -> Vec<(&'a Path, PlanDiff<'a>)> {
+        let target_path = |renames: &'a Renames, source_path: &Path| {
+            renames
+                .items
+                .get(source_path)
+                .and_then(|target| target.as_ref().ok())
+                .map(|target| target.path.as_path())
+        };
+        let source_paths: collections::BTreeSet<_> =
+            self.items.keys().chain(other.items.keys()).collect();
+        source_paths
+            .into_iter()
+            .filter_map(|source_path| {
+                let diff = match (
+                    target_path(self, source_path),
+                    target_path(other, source_path),
+                ) {
+                    (Some(a), Some(b)) if a == b => PlanDiff::Same(a),
+                    (Some(a), Some(b)) => PlanDiff::Different(a, b),
+                    (Some(a), None) => PlanDiff::OnlyInA(a),
+                    (None, Some(b)) => PlanDiff::OnlyInB(b),
+                    (None, None) => return None,
+                };
+                Some((source_path.as_path(), diff))
+            })
+            .collect()
+    }
+
+    pub fn iter(&self) -> btree_map::Iter<'_, PathBuf, Result<Target>> {
         self.items.iter()
     }
 
     pub fn len(&self) -> usize {
         self.items.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
 }
 
 impl IntoIterator for Renames {
-    type Item = (PathBuf, Result<PathBuf>);
+    type Item = (PathBuf, Result<Target>);
 
-    type IntoIter = btree_map::IntoIter<PathBuf, Result<PathBuf>>;
+    type IntoIter = btree_map::IntoIter<PathBuf, Result<Target>>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.items.into_iter()
     }
 }
 
+/// A planned rename.
+#[derive(Clone, Copy, Debug)]
+pub struct RenameOp<'a> {
+    pub source: &'a Path,
+    pub target: &'a Target,
+}
+
+impl<'a> RenameOp<'a> {
+    /// Applies the rename to the real filesystem.
+    pub fn apply(self, options: &ApplyOptions) -> Outcome<'a> {
+        self.apply_with(&StdFileOps, options)
+    }
+
+    /// Applies the rename with `ops`, e.g. to record it instead.
+    pub fn apply_with<O>(self, ops: &O, options: &ApplyOptions) -> Outcome<'a>
+    where
+        O: FileOps,
+    {
+        let trashed = if options.trash {
+            match ops.trash(&self.target.path) {
+                Ok(trashed) => trashed,
+                Err(error) => return Outcome::Failed { op: self, error },
+            }
+        } else {
+            false
+        };
+        match apply_rename_with_retries(ops, options, self.source, &self.target.path) {
+            Ok(()) => Outcome::Renamed { op: self, trashed },
+            Err(error) => Outcome::Failed { op: self, error },
+        }
+    }
+}
+
+/// Options deciding how planned renames are applied.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ApplyOptions {
+    /// Creates hard links to files instead of moving them.
+    pub hardlink: bool,
+    /// Restores the modification time of files after renaming them.
+    pub preserve_timestamps: bool,
+    /// Moves the files about to be overwritten to the trash first.
+    pub trash: bool,
+    /// How many times renames failing with transient errors are retried.
+    pub retries: u32,
+}
+
+/// What became of a planned rename once applied.
+#[derive(Debug)]
+pub enum Outcome<'a> {
+    Renamed {
+        op: RenameOp<'a>,
+        /// Whether the file previously at the target was trashed.
+        trashed: bool,
+    },
+    Failed {
+        op: RenameOp<'a>,
+        error: io::Error,
+    },
+}
+
+impl<'a> Outcome<'a> {
+    pub fn op(&self) -> RenameOp<'a> {
+        match self {
+            Self::Renamed { op, .. } | Self::Failed { op, .. } => *op,
+        }
+    }
+}
+
+fn link_file<O>(ops: &O, source_path: &Path, target_path: &Path) -> io::Result<()>
+where
+    O: FileOps,
+{
+    ops.hard_link(source_path, target_path)
+        .map_err(|err| match err.kind() {
+            io::ErrorKind::CrossesDevices => {
+                io::Error::new(err.kind(), "Source and target are on different filesystems")
+            }
+            _ => err,
+        })
+}
+
+fn map_locked_error(err: io::Error) -> io::Error {
+    if image::is_sharing_violation(&err) {
+        io::Error::new(err.kind(), image::Error::FileLocked.to_string())
+    } else {
+        err
+    }
+}
+
+/// Renames a single file, creating the parent directories of its target.
+pub fn apply_rename<O>(
+    ops: &O,
+    options: &ApplyOptions,
+    source_path: &Path,
+    target_path: &Path,
+) -> io::Result<()>
+where
+    O: FileOps,
+{
+    if let Some(parent_path) = target_path.parent() {
+        ops.create_dir_all(parent_path)?;
+    }
+    if options.hardlink {
+        return link_file(ops, source_path, target_path);
+    }
+    if !options.preserve_timestamps {
+        return ops
+            .rename(source_path, target_path)
+            .map_err(map_locked_error);
+    }
+    let mtime = ops.modified(source_path)?;
+    ops.rename(source_path, target_path)
+        .map_err(map_locked_error)?;
+    ops.set_modified(target_path, mtime)
+}
+
+/// Whether an error may go away on its own, e.g. on network shares.
+fn is_transient(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::ResourceBusy
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::TimedOut
+    )
+}
+
+/// Renames a single file, retrying with an exponential backoff as long as it
+/// fails with transient errors.
+fn apply_rename_with_retries<O>(
+    ops: &O,
+    options: &ApplyOptions,
+    source_path: &Path,
+    target_path: &Path,
+) -> io::Result<()>
+where
+    O: FileOps,
+{
+    let mut backoff = Duration::from_millis(100);
+    let mut attempt = 0;
+    loop {
+        match apply_rename(ops, options, source_path, target_path) {
+            Err(err) if attempt < options.retries && is_transient(&err) => {
+                tracing::warn!(
+                    "Can't rename {}, retrying in {}ms: {}",
+                    source_path.display(),
+                    backoff.as_millis(),
+                    err
+                );
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Applies a plan with `ops`, passing the outcome of every planned rename to
+/// `on_outcome` as soon as it is known, in the order of their source paths.
+pub fn apply_plan_with<'a, O, F>(
+    plan: &'a Renames,
+    ops: &O,
+    options: &ApplyOptions,
+    mut on_outcome: F,
+) -> PostApplyStats
+where
+    O: FileOps,
+    F: FnMut(Outcome<'a>),
+{
+    let start = Instant::now();
+    let mut renamed = 0;
+    let mut failed = 0;
+    for op in plan.iter_ops() {
+        let outcome = op.apply_with(ops, options);
+        match outcome {
+            Outcome::Renamed { .. } => renamed += 1,
+            Outcome::Failed { .. } => failed += 1,
+        }
+        on_outcome(outcome);
+    }
+    PostApplyStats {
+        renamed,
+        failed,
+        elapsed: start.elapsed(),
+    }
+}
+
+/// Applies a plan with `ops`, returning the outcome of every planned rename,
+/// in the order of their source paths.
+pub fn apply_plan<'a, O>(plan: &'a Renames, ops: &O, options: &ApplyOptions) -> Vec<Outcome<'a>>
+where
+    O: FileOps,
+{
+    let mut outcomes = Vec::new();
+    apply_plan_with(plan, ops, options, |outcome| outcomes.push(outcome));
+    outcomes
+}
+
+/// Which UTC offset wins when the one recorded in `OffsetTimeOriginal`
+/// differs from the one of the time zone.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OffsetConflict {
+    /// Uses the recorded offset
+    #[default]
+    PreferExif,
+    /// Uses the offset of the time zone
+    PreferArg,
+    /// Uses the recorded offset, with a warning
+    Warn,
+}
+
+/// What becomes of renames targeting files that already exist on disk.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OnExisting {
+    /// Skips the rename
+    #[default]
+    Skip,
+    /// Overwrites the existing file if the renamed one is older, by EXIF date
+    KeepOlder,
+    /// Overwrites the existing file if the renamed one is newer, by EXIF date
+    KeepNewer,
+}
+
+#[derive(Clone, Copy, Debug, Default, clap::ValueEnum)]
+pub enum Case {
+    Lower,
+    Upper,
+    #[default]
+    Keep,
+}
+
+impl Case {
+    fn apply(self, name: String) -> String {
+        match self {
+            Case::Lower => name.to_lowercase(),
+            Case::Upper => name.to_uppercase(),
+            Case::Keep => name,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum FlashFilter {
+    Fired,
+    NotFired,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum ColorSpaceFilter {
+    Srgb,
+    #[value(name = "adobergb")]
+    AdobeRgb,
+}
+
+/// A resolution in pixels, written `WIDTHxHEIGHT`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Resolution {
+    /// Returns whether an image of the given dimensions is at least this
+    /// resolution, in either orientation.
+    pub fn is_reached_by(&self, width: u32, height: u32) -> bool {
+        (width >= self.width && height >= self.height)
+            || (width >= self.height && height >= self.width)
+    }
+}
+
+#[derive(Debug)]
+pub struct ResolutionParseError(String);
+
+impl fmt::Display for ResolutionParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid resolution {:?}, expected WIDTHxHEIGHT", self.0)
+    }
+}
+
+impl error::Error for ResolutionParseError {}
+
+impl str::FromStr for Resolution {
+    type Err = ResolutionParseError;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        let invalid = || ResolutionParseError(s.to_string());
+        let (width, height) = s.split_once(['x', 'X']).ok_or_else(invalid)?;
+        Ok(Self {
+            width: width.parse().map_err(|_| invalid())?,
+            height: height.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub name_format: template::Template,
+    /// Template used for files without a `DateTimeOriginal` tag, rendered
+    /// against their modification time.
+    pub template_if_no_date: Option<template::Template>,
+    /// Case applied to the whole target name, extension included.
+    pub case: Case,
+    /// Skips files whose flash state does not match.
+    pub filter_flash: Option<FlashFilter>,
+    /// Skips files taken with another white balance mode.
+    pub filter_white_balance: Option<image::WhiteBalance>,
+    /// Skips files in another color space.
+    pub filter_color_space: Option<ColorSpaceFilter>,
+    /// Skips files below this resolution.
+    pub filter_min_resolution: Option<Resolution>,
+    /// Skips files whose `Software` tag matches this pattern.
+    pub skip_software_pattern: Option<glob::Pattern>,
+    /// Capacity of the buffer EXIF data is read through.
+    pub read_buffer: usize,
+    /// Sorts targets into subdirectories of their source directory.
+    pub bucket_by: Option<bucket::BucketBy>,
+    /// Skips files whose name already contains a date, in any format.
+    pub only_undated: bool,
+    /// Localizes dates at the UTC offset inferred from GPS tags, if any.
+    pub infer_offset: bool,
+    /// Decides between the UTC offset recorded in files and the one of the
+    /// time zone, when they differ.
+    pub offset_conflict: OffsetConflict,
+    /// Stops reading files once this many failed to be read, if not 0.
+    pub max_errors: usize,
+    /// Keeps source extensions as they are, instead of canonicalizing them.
+    pub keep_extension: bool,
+    /// Names dated files by the time elapsed since this reference, instead of
+    /// with `name_format`.
+    pub relative_to: Option<DateTime<FixedOffset>>,
+    /// Plans renames onto files that already exist, instead of skipping them.
+    pub overwrite: bool,
+    /// Plans hard links, which leave sources in place, instead of renames.
+    pub hardlink: bool,
+    /// Skips files whose name already starts with their `%Y-%m-%d` date.
+    pub only_if_different_day: bool,
+    /// Falls back to XMP dates for files without an EXIF date.
+    pub use_xmp: bool,
+    /// Falls back to the dates of `.xmp` sidecars for files without an EXIF
+    /// date.
+    pub use_xmp_sidecar: bool,
+    /// Absolute paths of the files processed by previous runs, which are
+    /// skipped.
+    pub processed: hash_set::HashSet<PathBuf>,
+    /// Treats files with advisory locks as locked, on Unix systems.
+    pub check_locks: bool,
+    /// Skips files smaller than this size in bytes.
+    pub min_size: Option<u64>,
+    /// Skips files dated on these days, which cameras reset their clock to.
+    pub bogus_dates: Vec<NaiveDate>,
+    /// Reports targets existing on disk as conflicts, instead of skipping
+    /// them.
+    pub existing_as_conflicts: bool,
+    /// Decides which renames targeting files existing on disk are skipped,
+    /// unless they are overwritten or reported as conflicts.
+    pub on_existing: OnExisting,
+    /// Localizes photos in the time zone of their GPS coordinates, falling
+    /// back to the time zone. Ignored without the `timezone-auto` feature.
+    pub timezone_auto: bool,
+}
+
+/// The default filename format, e.g. `2024-05-12T09:00:00+0200`.
+pub const DEFAULT_NAME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%z";
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            name_format: DEFAULT_NAME_FORMAT.parse().unwrap(),
+            template_if_no_date: None,
+            case: Case::default(),
+            filter_flash: None,
+            filter_white_balance: None,
+            filter_color_space: None,
+            filter_min_resolution: None,
+            skip_software_pattern: None,
+            read_buffer: image::DEFAULT_BUFFER_CAPACITY,
+            bucket_by: None,
+            only_undated: false,
+            infer_offset: false,
+            offset_conflict: OffsetConflict::default(),
+            max_errors: 0,
+            keep_extension: false,
+            relative_to: None,
+            overwrite: false,
+            hardlink: false,
+            only_if_different_day: false,
+            use_xmp: false,
+            use_xmp_sidecar: false,
+            processed: hash_set::HashSet::new(),
+            check_locks: false,
+            min_size: None,
+            bogus_dates: Vec::new(),
+            existing_as_conflicts: false,
+            on_existing: OnExisting::default(),
+            timezone_auto: false,
+        }
+    }
+}
+
 const JPEG_CANONICAL_EXTENSION: &str = "jpg";
 const JPEG_EXTENSIONS: [&str; 4] = [JPEG_CANONICAL_EXTENSION, "JPG", "jpeg", "JPEG"];
 const TIFF_CANONICAL_EXTENSION: &str = "tiff";
 const TIFF_EXTENSIONS: [&str; 4] = [TIFF_CANONICAL_EXTENSION, "tif", "TIF", "TIFF"];
 
-fn get_target_extension(source_path: &Path) -> Result<&str> {
+fn get_target_extension<'a>(source_path: &'a Path, options: &Options) -> Result<&'a str> {
     let source_extension = source_path
         .extension()
-        .and_then(OsStr::to_str)
-        .ok_or(Error::Skip(SkipError::Extension))?;
-    if JPEG_EXTENSIONS.contains(&source_extension) {
+        .ok_or(Error::Skip(SkipError::Extension))?
+        .to_str()
+        .ok_or(Error::Skip(SkipError::NonUtf8Extension))?;
+    let is_jpeg = JPEG_EXTENSIONS.contains(&source_extension);
+    let is_tiff = TIFF_EXTENSIONS.contains(&source_extension);
+    if options.keep_extension && (is_jpeg || is_tiff) {
+        Ok(source_extension)
+    } else if is_jpeg {
         Ok(JPEG_CANONICAL_EXTENSION)
-    } else if TIFF_EXTENSIONS.contains(&source_extension) {
+    } else if is_tiff {
         Ok(TIFF_CANONICAL_EXTENSION)
     } else {
         Err(Error::Skip(SkipError::Extension))
     }
 }
 
-fn get_target_file_stem<T>(source_path: &Path, timezone: &T, name_format: &str) -> Result<String>
+fn parse_digits(bytes: Option<&[u8]>) -> Option<u32> {
+    let bytes = bytes.filter(|bytes| bytes.iter().all(u8::is_ascii_digit))?;
+    std::str::from_utf8(bytes).ok()?.parse().ok()
+}
+
+/// Whether `bytes` start with a year, month and day, either contiguous or
+/// split by the same `-`, `_` or `.` separator.
+fn starts_with_date(bytes: &[u8]) -> bool {
+    let separator = match bytes.get(4) {
+        Some(b'-' | b'_' | b'.') => 1,
+        _ => 0,
+    };
+    let month_start = 4 + separator;
+    let day_start = month_start + 2 + separator;
+    if separator == 1 && bytes.get(month_start + 2) != bytes.get(4) {
+        return false;
+    }
+    parse_digits(bytes.get(..4)).is_some_and(|year| (1900..=2099).contains(&year))
+        && parse_digits(bytes.get(month_start..month_start + 2))
+            .is_some_and(|month| (1..=12).contains(&month))
+        && parse_digits(bytes.get(day_start..day_start + 2))
+            .is_some_and(|day| (1..=31).contains(&day))
+}
+
+fn is_dated(source_path: &Path) -> bool {
+    let file_stem = source_path
+        .file_stem()
+        .unwrap_or_default()
+        .as_encoded_bytes();
+    (0..file_stem.len()).any(|start| {
+        (start == 0 || !file_stem[start - 1].is_ascii_digit())
+            && starts_with_date(&file_stem[start..])
+    })
+}
+
+fn get_field(
+    source_path: &Path,
+    image: Option<&image::Image>,
+    field: template::Field,
+) -> Option<String> {
+    match field {
+        template::Field::OriginalName => source_path
+            .file_stem()
+            .map(|file_stem| file_stem.to_string_lossy().into_owned()),
+        template::Field::Size => fs::metadata(source_path)
+            .ok()
+            .map(|metadata| metadata.len().to_string()),
+        template::Field::Software => image.and_then(|image| image.get_software().ok()),
+        template::Field::Subsec => image.and_then(|image| image.get_subsec().ok()),
+        template::Field::AltitudeM => image
+            .and_then(|image| image.get_gps_altitude().ok())
+            .map(|altitude| format!("{:.0}", altitude)),
+        template::Field::Flash => image
+            .and_then(|image| image.get_flash().ok())
+            .map(|fired| u8::from(fired).to_string()),
+        template::Field::WhiteBalance => image
+            .and_then(|image| image.get_white_balance().ok())
+            .map(|white_balance| white_balance.to_string()),
+        template::Field::ColorSpace => image
+            .and_then(|image| image.get_color_space().ok())
+            .map(|color_space| color_space.to_string()),
+        template::Field::Orientation => image
+            .and_then(|image| image.get_orientation().ok())
+            .map(|orientation| orientation.to_string()),
+        // Rendered from the datetime.
+        template::Field::EpochDays | template::Field::Offset => None,
+    }
+}
+
+fn check_filters(image: &image::Image, options: &Options) -> Result<()> {
+    if let Some(pattern) = &options.skip_software_pattern {
+        if let Ok(software) = image.get_software() {
+            if pattern.matches(&software) {
+                return Err(Error::Skip(SkipError::FilterMismatch));
+            }
+        }
+    }
+    if let Some(filter) = options.filter_flash {
+        let fired = image.get_flash().ok();
+        if fired != Some(filter == FlashFilter::Fired) {
+            return Err(Error::Skip(SkipError::FilterMismatch));
+        }
+    }
+    if let Some(filter) = options.filter_white_balance {
+        if image.get_white_balance().ok() != Some(filter) {
+            return Err(Error::Skip(SkipError::FilterMismatch));
+        }
+    }
+    if let Some(filter) = options.filter_color_space {
+        let color_space = match filter {
+            ColorSpaceFilter::Srgb => image::ColorSpace::Srgb,
+            ColorSpaceFilter::AdobeRgb => image::ColorSpace::AdobeRgb,
+        };
+        if image.get_color_space().ok() != Some(color_space) {
+            return Err(Error::Skip(SkipError::FilterMismatch));
+        }
+    }
+    if let Some(resolution) = options.filter_min_resolution {
+        match image.get_pixel_dimensions() {
+            Ok((width, height)) if !resolution.is_reached_by(width, height) => {
+                return Err(Error::Skip(SkipError::BelowMinResolution));
+            }
+            Ok(_) => {}
+            Err(_) => return Err(Error::Skip(SkipError::FilterMismatch)),
+        }
+    }
+    Ok(())
+}
+
+fn get_fallback_file_stem<T>(
+    source_path: &Path,
+    image: Option<&image::Image>,
+    timezone: &T,
+    options: &Options,
+    err: image::Error,
+) -> Result<(String, DateTime<FixedOffset>, DateSource)>
+where
+    T: TimeZone,
+    T::Offset: fmt::Display,
+{
+    if err.is_missing_datetime() {
+        let mut xmp_datetime = None;
+        if options.use_xmp {
+            xmp_datetime = xmp::read_datetime(source_path)
+                .map_err(image::Error::from)?
+                .map(|naive_datetime| (naive_datetime, DateSource::Xmp));
+        }
+        if xmp_datetime.is_none() && options.use_xmp_sidecar {
+            xmp_datetime = xmp::read_sidecar_datetime(source_path)
+                .map_err(image::Error::from)?
+                .map(|naive_datetime| (naive_datetime, DateSource::XmpSidecar));
+        }
+        if let Some((naive_datetime, date_source)) = xmp_datetime {
+            let datetime = image::localize(&naive_datetime, timezone)?;
+            return Ok(render_file_stem(
+                source_path,
+                image,
+                &datetime,
+                date_source,
+                options,
+            ));
+        }
+    }
+    let template = match &options.template_if_no_date {
+        Some(template) if err.is_missing_datetime() => template,
+        _ => return Err(err.into()),
+    };
+    let modified = fs::metadata(source_path)
+        .and_then(|metadata| metadata.modified())
+        .map_err(image::Error::from)?;
+    let datetime = DateTime::<Utc>::from(modified).with_timezone(timezone);
+    let file_stem = template.render(&datetime, |field| get_field(source_path, image, field));
+    Ok((file_stem, datetime.fixed_offset(), DateSource::Mtime))
+}
+
+/// Formats a signed duration as `+HH:MM:SS`, hours growing past two digits
+/// if needed.
+fn format_elapsed(elapsed: TimeDelta) -> String {
+    let sign = if elapsed < TimeDelta::zero() {
+        '-'
+    } else {
+        '+'
+    };
+    let seconds = elapsed.num_seconds().unsigned_abs();
+    format!(
+        "{}{:02}:{:02}:{:02}",
+        sign,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn render_file_stem<T>(
+    source_path: &Path,
+    image: Option<&image::Image>,
+    datetime: &DateTime<T>,
+    date_source: DateSource,
+    options: &Options,
+) -> (String, DateTime<FixedOffset>, DateSource)
+where
+    T: TimeZone,
+    T::Offset: fmt::Display,
+{
+    let file_stem = match options.relative_to {
+        Some(reference) => format_elapsed(datetime.fixed_offset() - reference),
+        None => options
+            .name_format
+            .render(datetime, |field| get_field(source_path, image, field)),
+    };
+    (file_stem, datetime.fixed_offset(), date_source)
+}
+
+/// Returns the time zone at the given coordinates, from the boundaries
+/// bundled with `tzf-rs`.
+#[cfg(feature = "timezone-auto")]
+fn lookup_timezone(latitude: f64, longitude: f64) -> Option<chrono_tz::Tz> {
+    static FINDER: std::sync::OnceLock<tzf_rs::DefaultFinder> = std::sync::OnceLock::new();
+    FINDER
+        .get_or_init(tzf_rs::DefaultFinder::new)
+        .get_tz_name(longitude, latitude)
+        .parse()
+        .ok()
+}
+
+/// Returns the UTC offset of `timezone` at the original datetime of `image`,
+/// if it differs from `offset`.
+fn get_other_offset<T>(
+    image: &image::Image,
+    offset: FixedOffset,
+    timezone: &T,
+) -> Option<FixedOffset>
+where
+    T: TimeZone,
+{
+    let zone_offset = image.get_datetime(timezone).ok()?.offset().fix();
+    (zone_offset != offset).then_some(zone_offset)
+}
+
+fn get_target_file_stem<T>(
+    source_path: &Path,
+    timezone: &T,
+    options: &Options,
+) -> Result<(String, DateTime<FixedOffset>, DateSource)>
 where
     T: TimeZone,
     T::Offset: fmt::Display,
@@ -164,37 +1459,168 @@ where
     if source_path.is_dir() {
         return Err(Error::Skip(SkipError::Directory));
     }
-    let image = image::Image::open(source_path)?;
-    let datetime = image.get_datetime(timezone)?;
-    let file_stem = datetime.format(name_format).to_string();
-    Ok(file_stem)
+    if options.only_undated && is_dated(source_path) {
+        return Err(Error::Skip(SkipError::AlreadyDated));
+    }
+    if let Some(min_size) = options.min_size {
+        if fs::metadata(source_path).map_err(image::Error::Io)?.len() < min_size {
+            return Err(Error::Skip(SkipError::TooSmall));
+        }
+    }
+    if options.check_locks && image::is_locked(source_path)? {
+        return Err(image::Error::FileLocked.into());
+    }
+    let image = match image::Image::open_with_capacity(source_path, options.read_buffer) {
+        Ok(image) => image,
+        Err(err) => return get_fallback_file_stem(source_path, None, timezone, options, err),
+    };
+    check_filters(&image, options)?;
+    let recorded_offset = image.get_offset_time_original().ok();
+    #[cfg(feature = "timezone-auto")]
+    let gps_timezone = if options.timezone_auto {
+        image
+            .get_gps_coordinates()
+            .ok()
+            .and_then(|(latitude, longitude)| lookup_timezone(latitude, longitude))
+    } else {
+        None
+    };
+    #[cfg(not(feature = "timezone-auto"))]
+    let gps_timezone: Option<chrono_tz::Tz> = None;
+    let zone_offset = recorded_offset.and_then(|recorded_offset| match gps_timezone {
+        Some(gps_timezone) => get_other_offset(&image, recorded_offset, &gps_timezone),
+        None => get_other_offset(&image, recorded_offset, timezone),
+    });
+    // An offset recorded by the camera takes precedence over inferred ones,
+    // unless the time zone was preferred to it.
+    let offset = match (recorded_offset, zone_offset) {
+        (Some(recorded_offset), Some(zone_offset)) => {
+            let source_path = source_path.display();
+            match options.offset_conflict {
+                OffsetConflict::PreferExif => {
+                    tracing::debug!(
+                        "Using offset {} recorded in {} rather than {} from the time zone",
+                        recorded_offset,
+                        source_path,
+                        zone_offset
+                    );
+                    Some(recorded_offset)
+                }
+                OffsetConflict::Warn => {
+                    tracing::warn!(
+                        "Using offset {} recorded in {} rather than {} from the time zone",
+                        recorded_offset,
+                        source_path,
+                        zone_offset
+                    );
+                    Some(recorded_offset)
+                }
+                OffsetConflict::PreferArg => {
+                    tracing::debug!(
+                        "Using offset {} from the time zone rather than {} recorded in {}",
+                        zone_offset,
+                        recorded_offset,
+                        source_path
+                    );
+                    None
+                }
+            }
+        }
+        // The recorded offset is the one of the time zone, in which dates are
+        // localized to keep its abbreviation.
+        (Some(_), None) => None,
+        (None, _) if options.infer_offset => image.get_gps_offset().ok(),
+        (None, _) => None,
+    };
+    let date_source = DateSource::Tag(image::DATETIME_TAG);
+    let result = match (offset, gps_timezone) {
+        (Some(offset), _) => image.get_datetime(&offset).map(|datetime| {
+            render_file_stem(source_path, Some(&image), &datetime, date_source, options)
+        }),
+        (None, Some(gps_timezone)) => image.get_datetime(&gps_timezone).map(|datetime| {
+            render_file_stem(source_path, Some(&image), &datetime, date_source, options)
+        }),
+        (None, None) => image.get_datetime(timezone).map(|datetime| {
+            render_file_stem(source_path, Some(&image), &datetime, date_source, options)
+        }),
+    };
+    match result {
+        Ok(file_stem) => Ok(file_stem),
+        Err(err) => get_fallback_file_stem(source_path, Some(&image), timezone, options, err),
+    }
 }
 
-fn get_target_name<T>(source_path: &Path, timezone: &T, name_format: &str) -> Result<OsString>
+fn get_target_name<T>(
+    source_path: &Path,
+    timezone: &T,
+    options: &Options,
+) -> Result<(OsString, DateTime<FixedOffset>, DateSource)>
 where
     T: TimeZone,
     T::Offset: fmt::Display,
 {
-    let target_extension = get_target_extension(source_path)?;
-    let target_file_stem = get_target_file_stem(source_path, timezone, name_format)?;
+    let target_extension = get_target_extension(source_path, options)?;
+    let (target_file_stem, datetime, date_source) =
+        get_target_file_stem(source_path, timezone, options)?;
     let mut target_name = target_file_stem;
     target_name.push('.');
     target_name.push_str(target_extension);
-    Ok(OsString::from(target_name))
+    Ok((
+        OsString::from(options.case.apply(target_name)),
+        datetime,
+        date_source,
+    ))
 }
 
-fn get_target_path<T>(source_path: &Path, timezone: &T, name_format: &str) -> Result<PathBuf>
+fn get_target<T>(source_path: &Path, timezone: &T, options: &Options) -> Result<Target>
 where
     T: TimeZone,
     T::Offset: fmt::Display,
 {
-    let target_name = get_target_name(source_path, timezone, name_format)?;
-    let parent_path = source_path.parent().unwrap();
+    if !options.processed.is_empty()
+        && path::absolute(source_path).is_ok_and(|path| options.processed.contains(&path))
+    {
+        return Err(Error::Skip(SkipError::AlreadyProcessed));
+    }
+    let (target_name, datetime, date_source) = match get_target_name(source_path, timezone, options)
+    {
+        // The file was removed after the directory was listed.
+        Err(Error::Image(image::Error::Io(err))) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::Skip(SkipError::Vanished));
+        }
+        result => result?,
+    };
+    if options.bogus_dates.contains(&datetime.date_naive()) {
+        return Err(Error::Skip(SkipError::BogusDate));
+    }
+    if options.only_if_different_day {
+        let day = datetime.format("%Y-%m-%d").to_string();
+        let file_stem = source_path.file_stem().unwrap_or_default();
+        if file_stem.to_string_lossy().starts_with(&day) {
+            return Err(Error::Skip(SkipError::WellNamed));
+        }
+    }
+    let mut parent_path = source_path.parent().unwrap().to_path_buf();
+    if let Some(bucket_by) = options.bucket_by {
+        parent_path.push(bucket::bucket_path(&datetime, bucket_by));
+    }
     let target_path = parent_path.join(target_name);
     if source_path == target_path {
         return Err(Error::Skip(SkipError::WellNamed));
     }
-    Ok(target_path)
+    let size = match fs::metadata(source_path) {
+        Ok(metadata) => metadata.len(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::Skip(SkipError::Vanished));
+        }
+        Err(err) => return Err(image::Error::Io(err).into()),
+    };
+    Ok(Target {
+        path: target_path,
+        datetime,
+        date_source,
+        size,
+    })
 }
 
 fn get_source_paths(source_path: &Path) -> io::Result<Vec<PathBuf>> {
@@ -202,7 +1628,19 @@ fn get_source_paths(source_path: &Path) -> io::Result<Vec<PathBuf>> {
         let source_path = source_path.to_path_buf();
         return Ok(vec![source_path]);
     }
-    let read_dir = fs::read_dir(source_path)?;
+    let read_dir = fs::read_dir(source_path).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => io::Error::new(
+            err.kind(),
+            format!("Source path does not exist: {}", source_path.display()),
+        ),
+        // A file followed by a separator or other trailing components,
+        // e.g. `photo.jpg/`.
+        io::ErrorKind::NotADirectory => io::Error::new(
+            err.kind(),
+            format!("Source path is not a directory: {}", source_path.display()),
+        ),
+        _ => err,
+    })?;
     let paths: io::Result<Vec<_>> = read_dir
         .map(|result| result.map(|dir_entry| dir_entry.path()))
         .collect();
@@ -211,16 +1649,1089 @@ fn get_source_paths(source_path: &Path) -> io::Result<Vec<PathBuf>> {
     Ok(paths)
 }
 
-pub fn get_renames<T>(source_path: &Path, timezone: &T, name_format: &str) -> io::Result<Renames>
+/// Reads a list of source paths, one per line.
+///
+/// Surrounding whitespace is trimmed, and blank lines and lines starting with
+/// `#` are ignored.
+pub fn read_input_list(list_path: &Path) -> io::Result<Vec<PathBuf>> {
+    let contents = fs::read_to_string(list_path)?;
+    let paths = contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(PathBuf::from)
+        .collect();
+    Ok(paths)
+}
+
+/// Reads the absolute paths of the files processed by previous runs from a
+/// state file, if it exists.
+pub fn read_state(state_path: &Path) -> io::Result<hash_set::HashSet<PathBuf>> {
+    match read_input_list(state_path) {
+        Ok(paths) => Ok(paths.into_iter().collect()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(hash_set::HashSet::new()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Writes the absolute paths of processed files to a state file, one per line.
+pub fn write_state<'a, I>(state_path: &Path, paths: I) -> io::Result<()>
+where
+    I: IntoIterator<Item = &'a Path>,
+{
+    let mut paths = paths
+        .into_iter()
+        .map(path::absolute)
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+    paths.dedup();
+    let mut contents = String::new();
+    for path in paths {
+        contents.push_str(&path.to_string_lossy());
+        contents.push('\n');
+    }
+    fs::write(state_path, contents)
+}
+
+const PLAN_HEADER: [&str; 3] = ["source", "target", "datetime"];
+
+fn invalid_plan(line: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Invalid plan record on line {}", line),
+    )
+}
+
+/// Reads a plan written by `Renames::write_plan`.
+///
+/// Sources that no longer exist are skipped, as are targets that now exist
+/// unless `options.overwrite` is set.
+pub fn read_plan(plan_path: &Path, options: &Options) -> io::Result<Renames> {
+    let records = csv::read_records(&fs::read_to_string(plan_path)?)?;
+    let mut records = records.into_iter().enumerate();
+    match records.next() {
+        Some((_, header)) if header == PLAN_HEADER => {}
+        _ => return Err(invalid_plan(1)),
+    }
+    let mut renames = Renames::new(options);
+    for (i, record) in records {
+        let [source_path, target_path, datetime] =
+            <[String; 3]>::try_from(record).map_err(|_| invalid_plan(i + 1))?;
+        let datetime = DateTime::parse_from_rfc3339(&datetime).map_err(|_| invalid_plan(i + 1))?;
+        let source_path = PathBuf::from(source_path);
+        let target = match fs::symlink_metadata(&source_path) {
+            Ok(metadata) => Ok(Target {
+                path: PathBuf::from(target_path),
+                datetime,
+                date_source: DateSource::Plan,
+                size: metadata.len(),
+            }),
+            Err(_) => Err(Error::Skip(SkipError::Vanished)),
+        };
+        renames.insert_or_error(source_path, target);
+    }
+    renames.skip_existing();
+    Ok(renames)
+}
+
+fn get_renames_from_filtered<T, F, P, E>(
+    source_paths: Vec<PathBuf>,
+    timezone: &T,
+    options: &Options,
+    filter: F,
+    on_progress: P,
+    on_entry: E,
+) -> Renames
 where
     T: TimeZone + Sync,
     T::Offset: fmt::Display,
+    F: Fn(&Path) -> bool + Sync,
+    P: Fn(usize, usize) + Sync,
+    E: Fn(&Path, &Result<Target>) + Sync,
 {
-    let source_paths = get_source_paths(source_path)?;
+    let total = source_paths.len();
+    let completed = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
     let items = source_paths.into_par_iter().map(|source_path| {
-        let target_path = get_target_path(&source_path, timezone, name_format);
-        (source_path, target_path)
+        let too_many_errors =
+            options.max_errors > 0 && failed.load(Ordering::Relaxed) >= options.max_errors;
+        let target = if too_many_errors {
+            Err(Error::Skip(SkipError::TooManyErrors))
+        } else if filter(&source_path) {
+            get_target(&source_path, timezone, options)
+        } else {
+            Err(Error::Skip(SkipError::Filtered))
+        };
+        if target.as_ref().is_err_and(Error::is_failure) {
+            failed.fetch_add(1, Ordering::Relaxed);
+        }
+        on_entry(&source_path, &target);
+        on_progress(completed.fetch_add(1, Ordering::Relaxed) + 1, total);
+        (source_path, target)
     });
-    let items = btree_map::BTreeMap::from_par_iter(items);
-    Ok(Renames { items })
+    let items: Vec<_> = items.collect();
+    let mut renames = Renames::new(options);
+    for (source_path, target) in items {
+        renames.insert_or_error(source_path, target);
+    }
+    renames.skip_existing();
+    renames
+}
+
+pub fn get_renames_from<T>(source_paths: Vec<PathBuf>, timezone: &T, options: &Options) -> Renames
+where
+    T: TimeZone + Sync,
+    T::Offset: fmt::Display,
+{
+    get_renames_from_filtered(
+        source_paths,
+        timezone,
+        options,
+        |_| true,
+        |_, _| {},
+        |_, _| {},
+    )
+}
+
+/// Like `get_renames_from`, but calls `on_entry` with the entry of each file
+/// as soon as it is resolved, e.g. to stream the plan.
+///
+/// Files are processed in parallel, so `on_entry` may be called from several
+/// threads, and in any order. Entries are passed before the plan is complete:
+/// planned renames may still be skipped once all files are known, as their
+/// target exists on disk.
+pub fn get_renames_from_streamed<T, E>(
+    source_paths: Vec<PathBuf>,
+    timezone: &T,
+    options: &Options,
+    on_entry: E,
+) -> Renames
+where
+    T: TimeZone + Sync,
+    T::Offset: fmt::Display,
+    E: Fn(&Path, &Result<Target>) + Sync,
+{
+    get_renames_from_filtered(
+        source_paths,
+        timezone,
+        options,
+        |_| true,
+        |_, _| {},
+        on_entry,
+    )
+}
+
+pub fn get_renames<T>(source_path: &Path, timezone: &T, options: &Options) -> io::Result<Renames>
+where
+    T: TimeZone + Sync,
+    T::Offset: fmt::Display,
+{
+    let source_paths = get_source_paths(source_path)?;
+    Ok(get_renames_from(source_paths, timezone, options))
+}
+
+/// Like `get_renames`, but skips the source paths `filter` rejects without
+/// opening them.
+pub fn get_renames_filtered<T, F>(
+    source_path: &Path,
+    timezone: &T,
+    options: &Options,
+    filter: F,
+) -> io::Result<Renames>
+where
+    T: TimeZone + Sync,
+    T::Offset: fmt::Display,
+    F: Fn(&Path) -> bool + Sync,
+{
+    let source_paths = get_source_paths(source_path)?;
+    Ok(get_renames_from_filtered(
+        source_paths,
+        timezone,
+        options,
+        filter,
+        |_, _| {},
+        |_, _| {},
+    ))
+}
+
+/// Like `get_renames`, but calls `on_progress` with the number of files
+/// processed so far and the total number of files, after each file.
+///
+/// Files are processed in parallel, so `on_progress` may be called from
+/// several threads, and the counts it receives out of order.
+pub fn get_renames_with_progress<T, F>(
+    source_path: &Path,
+    timezone: &T,
+    options: &Options,
+    on_progress: F,
+) -> io::Result<Renames>
+where
+    T: TimeZone + Sync,
+    T::Offset: fmt::Display,
+    F: Fn(usize, usize) + Sync,
+{
+    let source_paths = get_source_paths(source_path)?;
+    Ok(get_renames_from_filtered(
+        source_paths,
+        timezone,
+        options,
+        |_| true,
+        on_progress,
+        |_, _| {},
+    ))
+}
+
+/// Like `get_renames_from_streamed`, for the files of `source_path`.
+pub fn get_renames_streamed<T, E>(
+    source_path: &Path,
+    timezone: &T,
+    options: &Options,
+    on_entry: E,
+) -> io::Result<Renames>
+where
+    T: TimeZone + Sync,
+    T::Offset: fmt::Display,
+    E: Fn(&Path, &Result<Target>) + Sync,
+{
+    let source_paths = get_source_paths(source_path)?;
+    Ok(get_renames_from_streamed(
+        source_paths,
+        timezone,
+        options,
+        on_entry,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use exif::{In, Tag};
+    use filetime::FileTime;
+
+    use super::*;
+    use crate::{
+        fileops::MemFileOps,
+        fixtures::{self, TempDir, Tiff, Value},
+    };
+
+    fn target<P>(path: P) -> Target
+    where
+        P: AsRef<Path>,
+    {
+        Target {
+            path: path.as_ref().to_path_buf(),
+            datetime: DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z").unwrap(),
+            date_source: DateSource::Plan,
+            size: 0,
+        }
+    }
+
+    fn plan(entries: Vec<(&str, Result<Target>)>) -> Renames {
+        let options = Options {
+            overwrite: true,
+            ..Options::default()
+        };
+        plan_with(&options, entries)
+    }
+
+    /// Builds a plan handling existing targets as `options` say.
+    fn plan_with<P>(options: &Options, entries: Vec<(P, Result<Target>)>) -> Renames
+    where
+        P: AsRef<Path>,
+    {
+        let mut renames = Renames::new(options);
+        for (source_path, target) in entries {
+            renames.insert_or_error(source_path.as_ref().to_path_buf(), target);
+        }
+        renames.skip_existing();
+        renames
+    }
+
+    #[test]
+    fn apply_rename_creates_target_directories() {
+        let ops = MemFileOps::with_files(&["photos/a.jpg"]);
+        let target_path = Path::new("photos/2024/a.jpg");
+        apply_rename(
+            &ops,
+            &ApplyOptions::default(),
+            Path::new("photos/a.jpg"),
+            target_path,
+        )
+        .unwrap();
+        assert!(ops.dirs.borrow().contains(Path::new("photos/2024")));
+        assert!(!ops.has_file("photos/a.jpg"));
+        assert!(ops.has_file(target_path));
+    }
+
+    #[test]
+    fn apply_rename_hard_links() {
+        let ops = MemFileOps::with_files(&["a.jpg"]);
+        let options = ApplyOptions {
+            hardlink: true,
+            ..ApplyOptions::default()
+        };
+        apply_rename(&ops, &options, Path::new("a.jpg"), Path::new("b.jpg")).unwrap();
+        assert!(ops.has_file("a.jpg"));
+        assert!(ops.has_file("b.jpg"));
+        let err = apply_rename(&ops, &options, Path::new("a.jpg"), Path::new("b.jpg")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn apply_rename_preserves_timestamps() {
+        let ops = MemFileOps::with_files(&["a.jpg"]);
+        let mtime = FileTime::from_unix_time(1_700_000_000, 0);
+        ops.set_modified(Path::new("a.jpg"), mtime).unwrap();
+        let options = ApplyOptions {
+            preserve_timestamps: true,
+            ..ApplyOptions::default()
+        };
+        apply_rename(&ops, &options, Path::new("a.jpg"), Path::new("b.jpg")).unwrap();
+        assert_eq!(ops.modified(Path::new("b.jpg")).unwrap(), mtime);
+    }
+
+    #[test]
+    fn apply_rename_fails_onto_directory() {
+        let ops = MemFileOps::with_files(&["a.jpg"]);
+        ops.create_dir_all(Path::new("b.jpg")).unwrap();
+        let err = apply_rename(
+            &ops,
+            &ApplyOptions::default(),
+            Path::new("a.jpg"),
+            Path::new("b.jpg"),
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::IsADirectory);
+        assert!(ops.has_file("a.jpg"));
+    }
+
+    #[test]
+    fn apply_plan_applies_planned_renames_with_options() {
+        let plan = plan(vec![
+            ("a.jpg", Ok(target("2024/a.jpg"))),
+            ("b.jpg", Err(SkipError::WellNamed.into())),
+            ("c.jpg", Ok(target("c.jpg/x.jpg"))),
+        ]);
+        let ops = MemFileOps::with_files(&["a.jpg", "b.jpg", "c.jpg"]);
+        ops.create_dir_all(Path::new("c.jpg/x.jpg")).unwrap();
+        let options = ApplyOptions {
+            hardlink: true,
+            ..ApplyOptions::default()
+        };
+        let outcomes = apply_plan(&plan, &ops, &options);
+        let sources: Vec<_> = outcomes.iter().map(|outcome| outcome.op().source).collect();
+        assert_eq!(sources, [Path::new("a.jpg"), Path::new("c.jpg")]);
+        assert!(matches!(outcomes[0], Outcome::Renamed { .. }));
+        assert!(matches!(outcomes[1], Outcome::Failed { .. }));
+        // Hard linked, so the source is kept.
+        assert!(ops.has_file("a.jpg"));
+        assert!(ops.has_file("2024/a.jpg"));
+        assert!(ops.has_file("b.jpg"));
+    }
+
+    #[test]
+    fn apply_plan_retries_transient_failures() {
+        let plan = plan(vec![("a.jpg", Ok(target("b.jpg")))]);
+        let ops = MemFileOps::with_files(&["a.jpg"]);
+        ops.fail_rename("a.jpg", &[io::ErrorKind::ResourceBusy]);
+        let outcomes = apply_plan(&plan, &ops, &ApplyOptions::default());
+        assert!(
+            matches!(&outcomes[0], Outcome::Failed { error, .. } if error.kind() == io::ErrorKind::ResourceBusy)
+        );
+
+        ops.fail_rename("a.jpg", &[io::ErrorKind::ResourceBusy]);
+        let options = ApplyOptions {
+            retries: 1,
+            ..ApplyOptions::default()
+        };
+        let outcomes = apply_plan(&plan, &ops, &options);
+        assert!(matches!(outcomes[0], Outcome::Renamed { .. }));
+        assert!(ops.has_file("b.jpg"));
+    }
+
+    #[test]
+    fn apply_plan_does_not_retry_permanent_failures() {
+        let plan = plan(vec![("a.jpg", Ok(target("b.jpg")))]);
+        let ops = MemFileOps::with_files(&["a.jpg"]);
+        ops.fail_rename("a.jpg", &[io::ErrorKind::PermissionDenied]);
+        let options = ApplyOptions {
+            retries: 3,
+            ..ApplyOptions::default()
+        };
+        let outcomes = apply_plan(&plan, &ops, &options);
+        assert!(matches!(outcomes[0], Outcome::Failed { .. }));
+        assert!(ops.has_file("a.jpg"));
+    }
+
+    #[test]
+    fn apply_plan_trashes_overwritten_targets() {
+        let plan = plan(vec![
+            ("a.jpg", Ok(target("x.jpg"))),
+            ("b.jpg", Ok(target("y.jpg"))),
+        ]);
+        let ops = MemFileOps::with_files(&["a.jpg", "b.jpg", "x.jpg"]);
+        let options = ApplyOptions {
+            trash: true,
+            ..ApplyOptions::default()
+        };
+        let trashed: Vec<_> = apply_plan(&plan, &ops, &options)
+            .into_iter()
+            .map(|outcome| match outcome {
+                Outcome::Renamed { trashed, .. } => trashed,
+                Outcome::Failed { error, .. } => panic!("{}", error),
+            })
+            .collect();
+        assert_eq!(trashed, [true, false]);
+        assert_eq!(*ops.trashed.borrow(), [PathBuf::from("x.jpg")]);
+    }
+
+    #[test]
+    fn apply_plan_with_reports_outcomes_in_order() {
+        let plan = plan(vec![
+            ("a.jpg", Ok(target("x.jpg"))),
+            ("b.jpg", Ok(target("y.jpg"))),
+        ]);
+        let ops = MemFileOps::with_files(&["a.jpg"]);
+        let mut reported = Vec::new();
+        let stats = apply_plan_with(&plan, &ops, &ApplyOptions::default(), |outcome| {
+            reported.push((
+                outcome.op().source,
+                matches!(outcome, Outcome::Renamed { .. }),
+            ));
+        });
+        assert_eq!(
+            reported,
+            [(Path::new("a.jpg"), true), (Path::new("b.jpg"), false)]
+        );
+        assert_eq!((stats.renamed, stats.failed), (1, 1));
+    }
+
+    #[test]
+    fn renames_files_on_disk() {
+        let dir = TempDir::new();
+        let source_path = dir.write(
+            "a.jpg",
+            Tiff::new()
+                .date_time_original("2024:01:02 03:04:05")
+                .to_jpeg(),
+        );
+        dir.write("notes.txt", "");
+        let plan = get_renames(dir.path(), &Utc, &Options::default()).unwrap();
+        assert_eq!(plan.len(), 2);
+        let outcomes = apply_plan(&plan, &StdFileOps, &ApplyOptions::default());
+        assert_eq!(outcomes.len(), 1);
+        let target_path = dir.path().join("2024-01-02T03:04:05+0000.jpg");
+        assert_eq!(outcomes[0].op().target.path, target_path);
+        assert!(!source_path.exists());
+        assert!(target_path.exists());
+    }
+
+    #[test]
+    fn renames_files_on_disk_preserving_timestamps() {
+        let dir = TempDir::new();
+        let source_path = dir.write(
+            "a.jpg",
+            Tiff::new()
+                .date_time_original("2024:01:02 03:04:05")
+                .to_jpeg(),
+        );
+        let mtime = FileTime::from_unix_time(1_600_000_000, 0);
+        filetime::set_file_mtime(&source_path, mtime).unwrap();
+        let plan = get_renames(dir.path(), &Utc, &Options::default()).unwrap();
+        let options = ApplyOptions {
+            preserve_timestamps: true,
+            ..ApplyOptions::default()
+        };
+        let outcomes = apply_plan(&plan, &StdFileOps, &options);
+        let target_path = &outcomes[0].op().target.path;
+        assert_eq!(StdFileOps.modified(target_path).unwrap(), mtime);
+    }
+
+    #[test]
+    fn to_csv_quotes_source_paths() {
+        let plan = plan(vec![
+            ("a, b.jpg", Ok(target("x.jpg"))),
+            ("c.jpg", Err(SkipError::WellNamed.into())),
+        ]);
+        let mut contents = Vec::new();
+        plan.to_csv(&mut contents).unwrap();
+        let contents = String::from_utf8(contents).unwrap();
+        assert!(contents.contains("\"a, b.jpg\",x.jpg,planned,\r\n"));
+        let records = csv::read_records(&contents).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1], ["a, b.jpg", "x.jpg", "planned", ""]);
+        assert_eq!(records[2][..3], ["c.jpg", "", "skipped"]);
+    }
+
+    #[test]
+    fn conflicts_are_sorted_by_path_then_source() {
+        let plan = plan(vec![
+            ("a.jpg", Ok(target("z.jpg"))),
+            ("b.jpg", Ok(target("y.jpg"))),
+            ("c.jpg", Ok(target("z.jpg"))),
+            ("d.jpg", Ok(target("y.jpg"))),
+            ("e.jpg", Ok(target("z.jpg"))),
+            ("k.jpg", Ok(target("m.jpg"))),
+            ("m.jpg", Ok(target("n.jpg"))),
+        ]);
+        let conflicts: Vec<_> = plan
+            .conflicts()
+            .map(|conflict| {
+                (
+                    conflict.side,
+                    conflict.path.to_str().unwrap(),
+                    conflict.source_path.to_str().unwrap(),
+                    conflict.other_source_path.unwrap().to_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            conflicts,
+            [
+                (Side::Source, "m.jpg", "m.jpg", "k.jpg"),
+                (Side::Target, "y.jpg", "d.jpg", "b.jpg"),
+                (Side::Target, "z.jpg", "c.jpg", "a.jpg"),
+                (Side::Target, "z.jpg", "e.jpg", "a.jpg"),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_files_with_trailing_separators() {
+        let dir = TempDir::new();
+        let source_path = dir.write("photo.jpg", Tiff::new().to_jpeg());
+        let mut path = source_path.into_os_string();
+        path.push(path::MAIN_SEPARATOR_STR);
+        let err = get_renames(Path::new(&path), &Utc, &Options::default())
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotADirectory);
+        assert!(err
+            .to_string()
+            .starts_with("Source path is not a directory"));
+    }
+
+    #[test]
+    fn rejects_missing_source_paths() {
+        let dir = TempDir::new();
+        let err = get_renames(&dir.path().join("missing"), &Utc, &Options::default())
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn renders_zero_padded_days_of_year() {
+        let dir = TempDir::new();
+        let dates = [
+            ("a.jpg", "2024:01:01 12:00:00", "2024_001"),
+            ("b.jpg", "2024:04:09 12:00:00", "2024_100"),
+            ("c.jpg", "2023:12:31 12:00:00", "2023_365"),
+            ("d.jpg", "2024:12:30 12:00:00", "2024_365"),
+            ("e.jpg", "2024:12:31 12:00:00", "2024_366"),
+        ];
+        for (name, datetime, _) in dates {
+            dir.write(name, Tiff::new().date_time_original(datetime).to_jpeg());
+        }
+        let options = Options {
+            name_format: "%Y_%j".parse().unwrap(),
+            ..Options::default()
+        };
+        let plan = get_renames(dir.path(), &Utc, &options).unwrap();
+        for (name, _, stem) in dates {
+            let target = plan.items[&dir.path().join(name)].as_ref().unwrap();
+            assert_eq!(target.path, dir.path().join(format!("{}.jpg", stem)));
+        }
+    }
+
+    #[test]
+    fn dates_xmp_only_files_with_use_xmp() {
+        let dir = TempDir::new();
+        let source_path = dir.write(
+            "a.jpg",
+            fixtures::jpeg(&[&fixtures::xmp(
+                "photoshop:DateCreated=\"2023-05-01T12:00:00\"",
+            )]),
+        );
+        let plan = get_renames(dir.path(), &Utc, &Options::default()).unwrap();
+        assert!(plan.items[&source_path].is_err());
+        let options = Options {
+            use_xmp: true,
+            ..Options::default()
+        };
+        let plan = get_renames(dir.path(), &Utc, &options).unwrap();
+        let target = plan.items[&source_path].as_ref().unwrap();
+        assert_eq!(target.path, dir.path().join("2023-05-01T12:00:00+0000.jpg"));
+        assert!(matches!(target.date_source, DateSource::Xmp));
+    }
+
+    #[test]
+    fn dates_files_from_xmp_sidecars() {
+        let dir = TempDir::new();
+        let source_path = dir.write("a.jpg", Tiff::new().to_jpeg());
+        dir.write(
+            "a.xmp",
+            fixtures::xmp_packet("xmp:CreateDate=\"2023-05-01T12:00:00\""),
+        );
+        let options = Options {
+            use_xmp: true,
+            use_xmp_sidecar: true,
+            ..Options::default()
+        };
+        let plan = get_renames(dir.path(), &Utc, &options).unwrap();
+        let target = plan.items[&source_path].as_ref().unwrap();
+        assert_eq!(target.path, dir.path().join("2023-05-01T12:00:00+0000.jpg"));
+        assert!(matches!(target.date_source, DateSource::XmpSidecar));
+        assert!(target.date_source.is_xmp());
+    }
+
+    #[test]
+    fn state_round_trips_absolute_paths() {
+        let dir = TempDir::new();
+        let state_path = dir.path().join("state");
+        let paths = [dir.path().join("b.jpg"), dir.path().join("a.jpg")];
+        write_state(
+            &state_path,
+            paths.iter().chain(&paths).map(PathBuf::as_path),
+        )
+        .unwrap();
+        let state = read_state(&state_path).unwrap();
+        assert_eq!(state, paths.into_iter().collect());
+    }
+
+    #[test]
+    fn offset_conflict_decides_between_offsets() {
+        let dir = TempDir::new();
+        let source_path = dir.write(
+            "a.jpg",
+            Tiff::new()
+                .date_time_original("2024:01:02 03:04:05")
+                .field(
+                    exif::In::PRIMARY,
+                    exif::Tag::OffsetTimeOriginal,
+                    fixtures::Value::ascii("+02:00"),
+                )
+                .to_jpeg(),
+        );
+        let timezone = FixedOffset::east_opt(3600).unwrap();
+        for (offset_conflict, expected) in [
+            (OffsetConflict::PreferExif, "2024-01-02T03:04:05+0200.jpg"),
+            (OffsetConflict::Warn, "2024-01-02T03:04:05+0200.jpg"),
+            (OffsetConflict::PreferArg, "2024-01-02T03:04:05+0100.jpg"),
+        ] {
+            let options = Options {
+                offset_conflict,
+                infer_offset: true,
+                ..Options::default()
+            };
+            let plan = get_renames(dir.path(), &timezone, &options).unwrap();
+            let target = plan.items[&source_path].as_ref().unwrap();
+            assert_eq!(
+                target.path,
+                dir.path().join(expected),
+                "{:?}",
+                offset_conflict
+            );
+        }
+    }
+
+    #[test]
+    fn stops_reading_files_after_max_errors() {
+        let dir = TempDir::new();
+        for name in ["a.jpg", "b.jpg", "c.jpg", "d.jpg"] {
+            dir.write(name, "not a JPEG file");
+        }
+        let options = Options {
+            max_errors: 2,
+            ..Options::default()
+        };
+        let plan = get_renames(dir.path(), &Utc, &options).unwrap();
+        let failed = plan
+            .iter()
+            .filter(|(_, target)| target.as_ref().is_err_and(Error::is_failure))
+            .count();
+        let unread = plan
+            .iter()
+            .filter(|(_, target)| matches!(target, Err(Error::Skip(SkipError::TooManyErrors))))
+            .count();
+        // Files being read in parallel may still fail past the limit.
+        assert!(failed >= 2);
+        assert_eq!(failed + unread, 4);
+    }
+
+    #[test]
+    fn diff_compares_plans_by_source() {
+        let a = plan(vec![
+            ("a.jpg", Ok(target("x.jpg"))),
+            ("b.jpg", Ok(target("y.jpg"))),
+            ("c.jpg", Ok(target("z.jpg"))),
+            ("d.jpg", Err(SkipError::WellNamed.into())),
+            ("f.jpg", Err(SkipError::WellNamed.into())),
+        ]);
+        let b = plan(vec![
+            ("a.jpg", Ok(target("x.jpg"))),
+            ("b.jpg", Ok(target("w.jpg"))),
+            ("c.jpg", Err(SkipError::WellNamed.into())),
+            ("d.jpg", Ok(target("v.jpg"))),
+            ("e.jpg", Ok(target("u.jpg"))),
+            ("f.jpg", Err(SkipError::WellNamed.into())),
+        ]);
+        assert_eq!(
+            a.diff(&b),
+            [
+                (Path::new("a.jpg"), PlanDiff::Same(Path::new("x.jpg"))),
+                (
+                    Path::new("b.jpg"),
+                    PlanDiff::Different(Path::new("y.jpg"), Path::new("w.jpg"))
+                ),
+                (Path::new("c.jpg"), PlanDiff::OnlyInA(Path::new("z.jpg"))),
+                (Path::new("d.jpg"), PlanDiff::OnlyInB(Path::new("v.jpg"))),
+                (Path::new("e.jpg"), PlanDiff::OnlyInB(Path::new("u.jpg"))),
+            ]
+        );
+    }
+
+    #[test]
+    fn on_existing_compares_exif_dates() {
+        let dir = TempDir::new();
+        let source_path = dir.write(
+            "a.jpg",
+            Tiff::new()
+                .date_time_original("2024:01:02 08:00:00")
+                .to_jpeg(),
+        );
+        dir.write(
+            "2024-01-02.jpg",
+            Tiff::new()
+                .date_time_original("2024:01:02 10:00:00")
+                .to_jpeg(),
+        );
+        for (on_existing, planned) in [
+            (OnExisting::Skip, false),
+            (OnExisting::KeepOlder, true),
+            (OnExisting::KeepNewer, false),
+        ] {
+            let options = Options {
+                name_format: "%Y-%m-%d".parse().unwrap(),
+                on_existing,
+                ..Options::default()
+            };
+            let plan = get_renames(dir.path(), &Utc, &options).unwrap();
+            let target = &plan.items[&source_path];
+            assert_eq!(target.is_ok(), planned, "{:?}", on_existing);
+            if !planned {
+                assert!(matches!(target, Err(Error::Skip(SkipError::AlreadyExists))));
+            }
+        }
+    }
+
+    #[test]
+    fn on_existing_keeps_undated_existing_files() {
+        let dir = TempDir::new();
+        let source_path = dir.write(
+            "a.jpg",
+            Tiff::new()
+                .date_time_original("2024:01:02 08:00:00")
+                .to_jpeg(),
+        );
+        dir.write("2024-01-02.jpg", Tiff::new().to_jpeg());
+        for on_existing in [OnExisting::KeepOlder, OnExisting::KeepNewer] {
+            let options = Options {
+                name_format: "%Y-%m-%d".parse().unwrap(),
+                on_existing,
+                ..Options::default()
+            };
+            let plan = get_renames(dir.path(), &Utc, &options).unwrap();
+            assert!(plan.items[&source_path].is_err());
+        }
+    }
+
+    #[test]
+    fn skips_renames_onto_sources_left_in_place() {
+        let dir = TempDir::new();
+        let a = dir.write("a.jpg", "a");
+        let b = dir.write("b.jpg", "b");
+        let c = dir.write("c.jpg", "c");
+        // a.jpg is not moved away, as c.jpg exists.
+        let plan = plan_with(
+            &Options::default(),
+            vec![(&a, Ok(target(&c))), (&b, Ok(target(&a)))],
+        );
+        for source_path in [&a, &b] {
+            assert!(matches!(
+                plan.items[source_path],
+                Err(Error::Skip(SkipError::AlreadyExists))
+            ));
+        }
+    }
+
+    #[test]
+    fn skip_duplicates_keeps_sources_left_in_place() {
+        let dir = TempDir::new();
+        let p = dir.write("p.jpg", "p");
+        let z2 = dir.write("z2024-01-02.jpg", "z2");
+        let s = dir.write("s.jpg", "s");
+        let z1 = dir.path().join("z2024-01-01.jpg");
+        let mut plan = plan_with(
+            &Options::default(),
+            vec![
+                (&p, Ok(target(&z1))),
+                (&z2, Ok(target(&z1))),
+                (&s, Ok(target(&z2))),
+            ],
+        );
+        assert_eq!(plan.skip_duplicates(), 1);
+        assert!(matches!(
+            plan.items[&z2],
+            Err(Error::Skip(SkipError::Duplicate))
+        ));
+        assert!(matches!(
+            plan.items[&s],
+            Err(Error::Skip(SkipError::AlreadyExists))
+        ));
+        assert_eq!(plan.conflicts().count(), 0);
+        apply_plan(&plan, &StdFileOps, &ApplyOptions::default());
+        for path in [&z1, &z2, &s] {
+            assert!(path.exists(), "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn retain_keeps_sources_left_in_place() {
+        let dir = TempDir::new();
+        let a = dir.write("a.jpg", "a");
+        let b = dir.write("b.jpg", "b");
+        let c = dir.path().join("c.jpg");
+        let entries = || vec![(&a, Ok(target(&c))), (&b, Ok(target(&a)))];
+
+        let mut plan = plan_with(&Options::default(), entries());
+        assert!(plan.items[&b].is_ok());
+        plan.retain(|source_path, _| source_path != a);
+        assert!(matches!(
+            plan.items[&b],
+            Err(Error::Skip(SkipError::AlreadyExists))
+        ));
+
+        let options = Options {
+            existing_as_conflicts: true,
+            ..Options::default()
+        };
+        let mut plan = plan_with(&options, entries());
+        assert_eq!(plan.conflicts().count(), 0);
+        plan.remove(&a);
+        let conflicts: Vec<_> = plan.conflicts().collect();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].path, a);
+    }
+
+    #[test]
+    fn hard_links_do_not_move_sources_away() {
+        let dir = TempDir::new();
+        let a = dir.write("a.jpg", "a");
+        let b = dir.write("b.jpg", "b");
+        let c = dir.path().join("c.jpg");
+        let entries = || vec![(&a, Ok(target(&c))), (&b, Ok(target(&a)))];
+
+        let options = Options {
+            hardlink: true,
+            ..Options::default()
+        };
+        let plan = plan_with(&options, entries());
+        assert!(plan.items[&a].is_ok());
+        assert!(matches!(
+            plan.items[&b],
+            Err(Error::Skip(SkipError::AlreadyExists))
+        ));
+
+        let options = Options {
+            hardlink: true,
+            existing_as_conflicts: true,
+            ..Options::default()
+        };
+        let plan = plan_with(&options, entries());
+        let conflicts: Vec<_> = plan.conflicts().collect();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].path, a);
+    }
+
+    #[test]
+    fn apply_plan_trashes_files_kept_over() {
+        let dir = TempDir::new();
+        let source_path = dir.write(
+            "a.jpg",
+            Tiff::new()
+                .date_time_original("2024:01:02 08:00:00")
+                .to_jpeg(),
+        );
+        let existing_path = dir.write(
+            "2024-01-02.jpg",
+            Tiff::new()
+                .date_time_original("2024:01:02 10:00:00")
+                .to_jpeg(),
+        );
+        let options = Options {
+            name_format: "%Y-%m-%d".parse().unwrap(),
+            on_existing: OnExisting::KeepOlder,
+            ..Options::default()
+        };
+        let plan = get_renames(dir.path(), &Utc, &options).unwrap();
+        let ops = MemFileOps::with_files(&[&source_path, &existing_path]);
+        let options = ApplyOptions {
+            trash: true,
+            ..ApplyOptions::default()
+        };
+        apply_plan(&plan, &ops, &options);
+        assert_eq!(*ops.trashed.borrow(), [existing_path.as_path()]);
+        assert!(ops.has_file(&existing_path));
+        assert!(!ops.has_file(&source_path));
+    }
+
+    #[test]
+    fn skip_duplicates_keeps_files_with_a_creation_time_first() {
+        let mut plan = plan(vec![
+            ("a.jpg", Ok(target("x.jpg"))),
+            ("b.jpg", Ok(target("x.jpg"))),
+            ("c.jpg", Ok(target("x.jpg"))),
+        ]);
+        let skipped = plan.skip_duplicates_by(|source_path| {
+            let secs = match source_path.to_str()? {
+                "b.jpg" => 2,
+                "c.jpg" => 1,
+                _ => return None,
+            };
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+        });
+        assert_eq!(skipped, 2);
+        assert!(plan.items[Path::new("c.jpg")].is_ok());
+        for source_path in ["a.jpg", "b.jpg"] {
+            assert!(matches!(
+                plan.items[Path::new(source_path)],
+                Err(Error::Skip(SkipError::Duplicate))
+            ));
+        }
+    }
+
+    #[test]
+    fn keeps_time_zone_abbreviations_matching_recorded_offsets() {
+        let dir = TempDir::new();
+        let recorded_path = dir.write(
+            "a.jpg",
+            Tiff::new()
+                .date_time_original("2024:07:01 03:04:05")
+                .field(In::PRIMARY, Tag::OffsetTimeOriginal, Value::ascii("+02:00"))
+                .to_jpeg(),
+        );
+        let unrecorded_path = dir.write(
+            "b.jpg",
+            Tiff::new()
+                .date_time_original("2024:07:01 03:04:06")
+                .to_jpeg(),
+        );
+        let options = Options {
+            name_format: "%H%M%S_%Z".parse().unwrap(),
+            ..Options::default()
+        };
+        let plan = get_renames(dir.path(), &chrono_tz::Europe::Paris, &options).unwrap();
+        for (source_path, target_name) in [
+            (&recorded_path, "030405_CEST.jpg"),
+            (&unrecorded_path, "030406_CEST.jpg"),
+        ] {
+            let target = plan.items[source_path].as_ref().unwrap();
+            assert_eq!(target.path, dir.path().join(target_name));
+        }
+    }
+
+    #[test]
+    fn renders_time_zone_abbreviations_across_dst() {
+        let dir = TempDir::new();
+        let cet_path = dir.write(
+            "a.jpg",
+            Tiff::new()
+                .date_time_original("2023:03:26 01:30:00")
+                .to_jpeg(),
+        );
+        let cest_path = dir.write(
+            "b.jpg",
+            Tiff::new()
+                .date_time_original("2023:03:26 03:30:00")
+                .to_jpeg(),
+        );
+        let options = Options {
+            name_format: "%H%M%S_%Z".parse().unwrap(),
+            ..Options::default()
+        };
+        let plan = get_renames(dir.path(), &chrono_tz::Europe::Paris, &options).unwrap();
+        for (source_path, target_name) in [
+            (&cet_path, "013000_CET.jpg"),
+            (&cest_path, "033000_CEST.jpg"),
+        ] {
+            let target = plan.items[source_path].as_ref().unwrap();
+            assert_eq!(target.path, dir.path().join(target_name));
+        }
+    }
+
+    #[test]
+    fn plans_single_files_like_one_file_directories() {
+        let dir = TempDir::new();
+        let source_path = dir.write(
+            "photos/photo.jpg",
+            Tiff::new()
+                .date_time_original("2024:01:02 03:04:05")
+                .to_jpeg(),
+        );
+        let options = Options::default();
+        let write_plan = |path: &Path| {
+            let plan = get_renames(path, &Utc, &options).unwrap();
+            let mut written = Vec::new();
+            plan.write_plan(&mut written).unwrap();
+            (written, plan.pre_apply_stats().to_string())
+        };
+        let (file_plan, file_stats) = write_plan(&source_path);
+        let (dir_plan, dir_stats) = write_plan(&dir.path().join("photos"));
+        assert_eq!(file_plan, dir_plan);
+        assert_eq!(file_stats, dir_stats);
+        assert!(String::from_utf8(file_plan)
+            .unwrap()
+            .contains("2024-01-02T03:04:05+0000.jpg"));
+    }
+
+    #[test]
+    fn streams_entries_before_handling_existing_targets() {
+        let dir = TempDir::new();
+        let source_path = dir.write(
+            "a.jpg",
+            Tiff::new()
+                .date_time_original("2024:01:02 03:04:05")
+                .to_jpeg(),
+        );
+        let other_path = dir.write("b.txt", "b");
+        dir.write("2024-01-02.jpg", "existing");
+        let options = Options {
+            name_format: "%Y-%m-%d".parse().unwrap(),
+            ..Options::default()
+        };
+        let streamed = std::sync::Mutex::new(Vec::new());
+        let plan = get_renames_streamed(dir.path(), &Utc, &options, |source_path, target| {
+            streamed
+                .lock()
+                .unwrap()
+                .push((source_path.to_path_buf(), target.is_ok()));
+        })
+        .unwrap();
+        let mut streamed = streamed.into_inner().unwrap();
+        streamed.sort();
+        assert_eq!(
+            streamed,
+            [
+                (dir.path().join("2024-01-02.jpg"), false),
+                (source_path.clone(), true),
+                (other_path, false),
+            ]
+        );
+        assert!(matches!(
+            plan.items[&source_path],
+            Err(Error::Skip(SkipError::AlreadyExists))
+        ));
+    }
 }
@@ -0,0 +1,359 @@
+use std::{fmt, path::MAIN_SEPARATOR, str::FromStr};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, NaiveDateTime, Offset, TimeZone,
+};
+use derive_more::{Display, Error};
+
+const UNKNOWN_VALUE: &str = "unknown";
+
+#[derive(Debug, Display, Error)]
+pub enum ParseError {
+    #[display(fmt = "Invalid format specifier in {:?}", _0)]
+    #[error(ignore)]
+    InvalidSpecifier(String),
+    #[display(fmt = "Unknown field {:?}", _0)]
+    #[error(ignore)]
+    UnknownField(String),
+    #[display(fmt = "Unclosed field")]
+    Unclosed,
+    #[display(fmt = "Unclosed section {:?}", _0)]
+    #[error(ignore)]
+    UnclosedSection(String),
+    #[display(fmt = "Unmatched section end {:?}", _0)]
+    #[error(ignore)]
+    UnmatchedSection(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    OriginalName,
+    Size,
+    Software,
+    /// Days elapsed since the Unix epoch, negative for earlier dates.
+    EpochDays,
+    /// The UTC offset, as rendered by `%z`.
+    Offset,
+    /// The `SubSecTimeOriginal` digits.
+    Subsec,
+    /// The GPS altitude, in whole meters.
+    AltitudeM,
+    /// `1` if the flash fired, `0` otherwise.
+    Flash,
+    /// The white balance mode, `auto` or `manual`.
+    WhiteBalance,
+    /// The color space, `srgb`, `adobergb` or the raw EXIF value.
+    ColorSpace,
+    /// The orientation, `landscape`, `portrait` or the raw EXIF value.
+    Orientation,
+}
+
+impl FromStr for Field {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "original_name" => Ok(Field::OriginalName),
+            "size" => Ok(Field::Size),
+            "software" => Ok(Field::Software),
+            "epoch_days" => Ok(Field::EpochDays),
+            "offset" => Ok(Field::Offset),
+            "subsec" => Ok(Field::Subsec),
+            "altitude_m" => Ok(Field::AltitudeM),
+            "flash" => Ok(Field::Flash),
+            "wb" => Ok(Field::WhiteBalance),
+            "colorspace" => Ok(Field::ColorSpace),
+            "orientation" => Ok(Field::Orientation),
+            _ => Err(ParseError::UnknownField(s.to_string())),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Text(String),
+    Field(Field),
+    /// Segments rendered only when the field is present and nonzero.
+    Section(Field, Vec<Segment>),
+}
+
+/// A filename template.
+///
+/// Templates are `strftime` format strings, in which `{field}` placeholders
+/// are replaced by file metadata. Text enclosed in `{?field}` and `{/field}`
+/// is omitted when the field is absent or zero, e.g. `{?offset}%z{/offset}`.
+/// Literal braces are written `{{` and `}}`.
+#[derive(Clone, Debug)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+fn push_text(segments: &mut Vec<Segment>, text: &mut String) -> Result<(), ParseError> {
+    if text.is_empty() {
+        return Ok(());
+    }
+    if StrftimeItems::new(text).any(|item| matches!(item, Item::Error)) {
+        return Err(ParseError::InvalidSpecifier(text.clone()));
+    }
+    segments.push(Segment::Text(std::mem::take(text)));
+    Ok(())
+}
+
+/// A section being parsed, along with the name it was opened with.
+struct OpenSection {
+    field: Field,
+    name: String,
+    segments: Vec<Segment>,
+}
+
+impl FromStr for Template {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut sections: Vec<OpenSection> = Vec::new();
+        let mut text = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            None => return Err(ParseError::Unclosed),
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                        }
+                    }
+                    let current = match sections.last_mut() {
+                        Some(section) => &mut section.segments,
+                        None => &mut segments,
+                    };
+                    push_text(current, &mut text)?;
+                    if let Some(name) = name.strip_prefix('?') {
+                        sections.push(OpenSection {
+                            field: name.parse()?,
+                            name: name.to_string(),
+                            segments: Vec::new(),
+                        });
+                    } else if let Some(name) = name.strip_prefix('/') {
+                        let section = match sections.pop() {
+                            Some(section) if section.name == name => section,
+                            _ => return Err(ParseError::UnmatchedSection(name.to_string())),
+                        };
+                        let parent = match sections.last_mut() {
+                            Some(parent) => &mut parent.segments,
+                            None => &mut segments,
+                        };
+                        parent.push(Segment::Section(section.field, section.segments));
+                    } else {
+                        current.push(Segment::Field(name.parse()?));
+                    }
+                }
+                _ => text.push(c),
+            }
+        }
+        if let Some(section) = sections.pop() {
+            return Err(ParseError::UnclosedSection(section.name));
+        }
+        push_text(&mut segments, &mut text)?;
+        Ok(Self { segments })
+    }
+}
+
+fn epoch_days<T>(datetime: &DateTime<T>) -> i64
+where
+    T: TimeZone,
+{
+    datetime
+        .date_naive()
+        .signed_duration_since(NaiveDateTime::UNIX_EPOCH.date())
+        .num_days()
+}
+
+fn sanitize(value: &str) -> String {
+    value.replace(['/', MAIN_SEPARATOR, '\0'], "_")
+}
+
+fn get_value<T, F>(field: Field, datetime: &DateTime<T>, fields: &F) -> Option<String>
+where
+    T: TimeZone,
+    T::Offset: fmt::Display,
+    F: Fn(Field) -> Option<String>,
+{
+    match field {
+        Field::EpochDays => Some(epoch_days(datetime).to_string()),
+        Field::Offset => Some(datetime.format("%z").to_string()),
+        _ => fields(field),
+    }
+}
+
+fn is_present<T, F>(field: Field, datetime: &DateTime<T>, fields: &F) -> bool
+where
+    T: TimeZone,
+    T::Offset: fmt::Display,
+    F: Fn(Field) -> Option<String>,
+{
+    match field {
+        Field::Offset => datetime.offset().fix().local_minus_utc() != 0,
+        _ => {
+            get_value(field, datetime, fields).is_some_and(|value| value.chars().any(|c| c != '0'))
+        }
+    }
+}
+
+fn render_segments<T, F>(
+    segments: &[Segment],
+    datetime: &DateTime<T>,
+    fields: &F,
+    rendered: &mut String,
+) where
+    T: TimeZone,
+    T::Offset: fmt::Display,
+    F: Fn(Field) -> Option<String>,
+{
+    for segment in segments {
+        match segment {
+            Segment::Text(text) => rendered.push_str(&datetime.format(text).to_string()),
+            Segment::Field(field) => match get_value(*field, datetime, fields) {
+                Some(value) => rendered.push_str(&sanitize(&value)),
+                None => rendered.push_str(UNKNOWN_VALUE),
+            },
+            Segment::Section(field, segments) => {
+                if is_present(*field, datetime, fields) {
+                    render_segments(segments, datetime, fields, rendered);
+                }
+            }
+        }
+    }
+}
+
+impl Template {
+    /// Renders the template for the given datetime.
+    ///
+    /// Fields derived from the datetime are computed directly; other values are
+    /// looked up with `fields`, and missing ones are rendered as `unknown`.
+    pub fn render<T, F>(&self, datetime: &DateTime<T>, fields: F) -> String
+    where
+        T: TimeZone,
+        T::Offset: fmt::Display,
+        F: Fn(Field) -> Option<String>,
+    {
+        let mut rendered = String::new();
+        render_segments(&self.segments, datetime, &fields, &mut rendered);
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{FixedOffset, TimeZone, Utc};
+
+    use super::*;
+
+    fn render_epoch_days<T>(datetime: &DateTime<T>) -> String
+    where
+        T: TimeZone,
+        T::Offset: fmt::Display,
+    {
+        let template: Template = "{epoch_days}".parse().unwrap();
+        template.render(datetime, |_| None)
+    }
+
+    #[test]
+    fn renders_epoch_days_around_the_epoch() {
+        let cases = [
+            ((1970, 1, 1, 0, 0, 0), "0"),
+            ((1970, 1, 1, 23, 59, 59), "0"),
+            ((1970, 1, 2, 0, 0, 0), "1"),
+            ((1969, 12, 31, 23, 59, 59), "-1"),
+            ((1900, 1, 1, 0, 0, 0), "-25567"),
+            ((2024, 2, 29, 12, 0, 0), "19782"),
+        ];
+        for ((year, month, day, hour, min, sec), expected) in cases {
+            let datetime = Utc
+                .with_ymd_and_hms(year, month, day, hour, min, sec)
+                .unwrap();
+            assert_eq!(render_epoch_days(&datetime), expected, "{}", datetime);
+        }
+    }
+
+    #[test]
+    fn renders_epoch_days_from_the_local_date() {
+        // Still 1969 in UTC.
+        let datetime = FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(1970, 1, 1, 0, 30, 0)
+            .unwrap();
+        assert_eq!(render_epoch_days(&datetime), "0");
+        let datetime = FixedOffset::west_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(1969, 12, 31, 23, 30, 0)
+            .unwrap();
+        assert_eq!(render_epoch_days(&datetime), "-1");
+    }
+
+    fn render(format: &str, fields: &[(Field, &str)]) -> String {
+        let template: Template = format.parse().unwrap();
+        let datetime = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        template.render(&datetime, |field| {
+            fields
+                .iter()
+                .find(|(other, _)| *other == field)
+                .map(|(_, value)| value.to_string())
+        })
+    }
+
+    #[test]
+    fn renders_sections_with_present_fields() {
+        assert_eq!(
+            render(
+                "%Y{?software}_{software}{/software}",
+                &[(Field::Software, "cam")]
+            ),
+            "2024_cam"
+        );
+    }
+
+    #[test]
+    fn omits_sections_with_missing_or_zero_fields() {
+        assert_eq!(render("%Y{?software}_{software}{/software}", &[]), "2024");
+        assert_eq!(
+            render("%Y{?flash}_flash{/flash}", &[(Field::Flash, "0")]),
+            "2024"
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_sections() {
+        assert!(matches!(
+            "%Y{?software}_{software}".parse::<Template>(),
+            Err(ParseError::UnclosedSection(name)) if name == "software"
+        ));
+    }
+
+    #[test]
+    fn rejects_mismatched_section_ends() {
+        assert!(matches!(
+            "%Y{?software}_{software}{/flash}".parse::<Template>(),
+            Err(ParseError::UnmatchedSection(name)) if name == "flash"
+        ));
+    }
+
+    #[test]
+    fn renders_escaped_braces() {
+        assert_eq!(
+            render("{{%Y}}_{{{software}}}", &[(Field::Software, "cam")]),
+            "{2024}_{cam}"
+        );
+    }
+}
@@ -0,0 +1,138 @@
+use std::{fs, io, path::Path};
+
+use chrono::NaiveDateTime;
+
+const PACKET_START: &[u8] = b"<x:xmpmeta";
+const PACKET_END: &[u8] = b"</x:xmpmeta>";
+
+/// The properties photos are dated from, by order of preference.
+const DATETIME_PROPERTIES: [&str; 2] = ["photoshop:DateCreated", "xmp:CreateDate"];
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Extracts the XMP packet embedded in a file, if any.
+fn get_packet(bytes: &[u8]) -> Option<&str> {
+    let start = find(bytes, PACKET_START)?;
+    let end = start + find(&bytes[start..], PACKET_END)? + PACKET_END.len();
+    std::str::from_utf8(&bytes[start..end]).ok()
+}
+
+/// Reads a simple property, written either as an attribute or as an element.
+fn get_property<'a>(packet: &'a str, name: &str) -> Option<&'a str> {
+    let attribute = format!("{}=", name);
+    if let Some(start) = packet.find(&attribute) {
+        let value = &packet[start + attribute.len()..];
+        let quote = value.chars().next().filter(|c| matches!(c, '"' | '\''))?;
+        let value = &value[1..];
+        return value.find(quote).map(|end| &value[..end]);
+    }
+    let element = format!("<{}>", name);
+    let start = packet.find(&element)? + element.len();
+    let value = &packet[start..];
+    value.find('<').map(|end| value[..end].trim())
+}
+
+/// Parses the local part of an XMP date, e.g. `2023-05-01T12:00:00+02:00`.
+///
+/// Dates without a time are too imprecise to name photos after, and rejected.
+fn parse_datetime(value: &str) -> Option<NaiveDateTime> {
+    value
+        .get(..19)
+        .and_then(|value| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").ok())
+        .or_else(|| {
+            value
+                .get(..16)
+                .and_then(|value| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M").ok())
+        })
+}
+
+/// Reads the capture datetime from the XMP packet embedded in a file.
+pub fn read_datetime(path: &Path) -> io::Result<Option<NaiveDateTime>> {
+    let bytes = fs::read(path)?;
+    let packet = match get_packet(&bytes) {
+        Some(packet) => packet,
+        None => return Ok(None),
+    };
+    Ok(DATETIME_PROPERTIES
+        .iter()
+        .filter_map(|name| get_property(packet, name))
+        .find_map(parse_datetime))
+}
+
+/// Reads the capture datetime from the `.xmp` sidecar of a file, if any.
+pub fn read_sidecar_datetime(path: &Path) -> io::Result<Option<NaiveDateTime>> {
+    match read_datetime(&path.with_extension("xmp")) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{self, TempDir, Tiff};
+
+    fn naive(datetime: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(datetime, "%Y-%m-%dT%H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn reads_datetime_from_xmp_only_jpeg() {
+        let dir = TempDir::new();
+        let path = dir.write(
+            "a.jpg",
+            fixtures::jpeg(&[&fixtures::xmp(
+                "xmp:CreateDate=\"2020-01-01T00:00:00\" \
+                 photoshop:DateCreated=\"2023-05-01T12:00:00+02:00\"",
+            )]),
+        );
+        assert_eq!(
+            read_datetime(&path).unwrap(),
+            Some(naive("2023-05-01T12:00:00"))
+        );
+    }
+
+    #[test]
+    fn reads_properties_written_as_elements() {
+        let packet = "<x:xmpmeta><xmp:CreateDate> 2023-05-01T12:00 </xmp:CreateDate></x:xmpmeta>";
+        assert_eq!(
+            get_property(packet, "xmp:CreateDate"),
+            Some("2023-05-01T12:00")
+        );
+        assert_eq!(
+            get_property(packet, "xmp:CreateDate").and_then(parse_datetime),
+            Some(naive("2023-05-01T12:00:00"))
+        );
+    }
+
+    #[test]
+    fn rejects_dates_without_time() {
+        assert_eq!(parse_datetime("2023-05-01"), None);
+    }
+
+    #[test]
+    fn ignores_files_without_packet() {
+        let dir = TempDir::new();
+        let path = dir.write("a.jpg", Tiff::new().to_jpeg());
+        assert_eq!(read_datetime(&path).unwrap(), None);
+        assert_eq!(read_sidecar_datetime(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn reads_datetime_from_sidecar() {
+        let dir = TempDir::new();
+        let path = dir.write("a.jpg", Tiff::new().to_jpeg());
+        dir.write(
+            "a.xmp",
+            fixtures::xmp_packet("xmp:CreateDate=\"2023-05-01T12:00:00\""),
+        );
+        assert_eq!(
+            read_sidecar_datetime(&path).unwrap(),
+            Some(naive("2023-05-01T12:00:00"))
+        );
+    }
+}